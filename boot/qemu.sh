@@ -4,6 +4,6 @@ TOP=$(git rev-parse --show-toplevel)
 $TOP/bin/qemu-system-aarch64 \
     -nographic \
     -M raspi3 \
-    -serial null -serial pty \
+    -serial pty -serial pty \
     -kernel \
     "$@"
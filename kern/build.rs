@@ -1,3 +1,50 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// The symbols of the previous build of the kernel, as listed by `nm`. The
+/// `build` target of the Makefile writes them, then builds the kernel again
+/// to embed them: the table sits after `.text`, so code addresses do not move.
+const SYMBOLS: &str = "build/kernel.sym";
+
+/// Encodes the code symbols listed in `nm` output, sorted by address: each
+/// is its address as a little-endian `u64`, the length of its name as a
+/// little-endian `u16`, and its name.
+fn encode(nm: &str) -> Vec<u8> {
+    let mut symbols: Vec<(u64, &str)> = nm
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let addr = u64::from_str_radix(fields.next()?, 16).ok()?;
+            match fields.next()? {
+                "t" | "T" => Some((addr, fields.next()?)),
+                _ => None,
+            }
+        })
+        .collect();
+    symbols.sort();
+
+    let mut table = Vec::new();
+    for (addr, name) in symbols {
+        let name = &name.as_bytes()[..name.len().min(u16::max_value() as usize)];
+        table.extend_from_slice(&addr.to_le_bytes());
+        table.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        table.extend_from_slice(name);
+    }
+    table
+}
+
 pub fn main() {
     println!("cargo:rerun-if-changed=.cargo/layout.ld");
+    println!("cargo:rerun-if-changed={}", SYMBOLS);
+
+    let table = fs::read_to_string(SYMBOLS).map(|nm| encode(&nm)).unwrap_or_default();
+    let out = env::var("OUT_DIR").unwrap();
+    let bin = Path::new(&out).join("ksyms.bin");
+    fs::write(&bin, &table).unwrap();
+    fs::write(Path::new(&out).join("ksyms.rs"), format!(
+        "#[used]\n\
+         #[cfg_attr(not(test), link_section = \".ksyms\")]\n\
+         static KSYMS: [u8; {}] = *include_bytes!({:?});\n",
+        table.len(), bin.display().to_string())).unwrap();
 }
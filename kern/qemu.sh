@@ -1,9 +1,21 @@
 #!/bin/sh
 
+# The first serial port is the PL011, the second one the mini UART. The console
+# uses the mini UART unless the kernel is built with the `pl011-console`
+# feature, in which case run with SERIAL="-serial mon:stdio -serial null".
+#
+# With the `gdbstub` feature, GDB attaches to the other UART, e.g. the PL011
+# with SERIAL="-serial tcp::1234,server,nowait -serial mon:stdio": stop the
+# kernel with the shell's `debug` command, then `target remote :1234`.
+#
+# The framebuffer console can be inspected headless from the monitor
+# (Ctrl-a c): `screendump fb.ppm`.
+SERIAL=${SERIAL:-"-serial null -serial mon:stdio"}
+
 TOP=$(git rev-parse --show-toplevel)
 $TOP/bin/qemu-system-aarch64 \
     -nographic \
     -M raspi3 \
-    -serial null -serial mon:stdio \
+    $SERIAL \
     -kernel \
     "$@"
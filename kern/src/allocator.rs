@@ -3,8 +3,13 @@ pub mod util;
 
 mod bin;
 mod bump;
+pub mod map;
+pub mod slab;
+pub mod stats;
+#[cfg(feature = "heap-trace")]
+pub mod trace;
 
-type AllocatorImpl = bin::Allocator;
+type AllocatorImpl = slab::Allocator;
 
 #[cfg(test)]
 mod tests;
@@ -15,6 +20,15 @@ use core::fmt;
 use crate::console::kprintln;
 use crate::mutex::Mutex;
 use pi::atags::{Atag, Atags};
+use pi::common::{IO_BASE, IO_BASE_END};
+use pi::fdt::DeviceTree;
+use pi::mailbox;
+
+use self::map::{MemoryMap, Region};
+use self::slab::CacheStats;
+use self::stats::HeapStats;
+#[cfg(feature = "heap-trace")]
+use self::trace::{SiteSummary, Tracer};
 
 /// `LocalAlloc` is an analogous trait to the standard library's `GlobalAlloc`,
 /// but it takes `&mut self` in `alloc()` and `dealloc()`.
@@ -24,7 +38,12 @@ pub trait LocalAlloc {
 }
 
 /// Thread-safe (locking) wrapper around a particular memory allocator.
-pub struct Allocator(Mutex<Option<AllocatorImpl>>);
+pub struct Allocator {
+    inner: Mutex<Option<AllocatorImpl>>,
+    stats: Mutex<HeapStats>,
+    #[cfg(feature = "heap-trace")]
+    trace: Mutex<Tracer>,
+}
 
 impl Allocator {
     /// Returns an uninitialized `Allocator`.
@@ -32,7 +51,12 @@ impl Allocator {
     /// The allocator must be initialized by calling `initialize()` before the
     /// first memory allocation. Failure to do will result in panics.
     pub const fn uninitialized() -> Self {
-        Allocator(Mutex::new(None))
+        Allocator {
+            inner: Mutex::new(None),
+            stats: Mutex::new(HeapStats::new()),
+            #[cfg(feature = "heap-trace")]
+            trace: Mutex::new(Tracer::new()),
+        }
     }
 
     /// Initializes the memory allocator.
@@ -43,26 +67,77 @@ impl Allocator {
     ///
     /// Panics if the system's memory map could not be retrieved.
     pub unsafe fn initialize(&self) {
-        let (start, end) = memory_map().expect("failed to find memory map");
-        *self.0.lock() = Some(AllocatorImpl::new(start, end));
+        let map = memory_map().expect("failed to find memory map");
+        let mut regions = map.regions().iter();
+        let first = regions.next().expect("no usable memory");
+        let mut allocator = AllocatorImpl::new(first.start, first.end);
+        for region in regions {
+            allocator.add_region(region.start, region.end);
+        }
+        *self.inner.lock() = Some(allocator);
+    }
+
+    /// Returns every empty slab held by the object caches to the backing
+    /// allocator. Returns the number of slabs released.
+    pub fn shrink(&self) -> usize {
+        self.inner
+            .lock()
+            .as_mut()
+            .expect("allocator uninitialized")
+            .shrink()
+    }
+
+    /// Returns a snapshot of the heap statistics.
+    pub fn stats(&self) -> HeapStats {
+        *self.stats.lock()
+    }
+
+    /// Calls `f` with the statistics of each slab object cache.
+    pub fn for_each_cache<F: FnMut(CacheStats)>(&self, f: F) {
+        if let Some(alloc) = self.inner.lock().as_ref() {
+            alloc.stats().for_each(f);
+        }
+    }
+
+    /// Calls `f` with a summary of the live allocations made from each call
+    /// site. Returns the number of live allocations that could not be traced.
+    #[cfg(feature = "heap-trace")]
+    pub fn for_each_site<F: FnMut(&SiteSummary)>(&self, f: F) -> usize {
+        let trace = self.trace.lock();
+        trace.for_each_site(f);
+        trace.untraced()
     }
 }
 
 unsafe impl GlobalAlloc for Allocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.0
+        let ptr = self.inner
             .lock()
             .as_mut()
             .expect("allocator uninitialized")
-            .alloc(layout)
+            .alloc(layout);
+
+        self.stats.lock().record_alloc(&layout, !ptr.is_null());
+        #[cfg(feature = "heap-trace")]
+        {
+            if !ptr.is_null() {
+                let frames = trace::call_stack();
+                self.trace.lock().insert(ptr, layout.size(), frames);
+            }
+        }
+        ptr
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        self.0
+        self.inner
             .lock()
             .as_mut()
             .expect("allocator uninitialized")
             .dealloc(ptr, layout);
+
+        self.stats.lock().record_dealloc(&layout);
+        #[cfg(feature = "heap-trace")]
+        self.trace.lock().remove(ptr);
     }
 }
 
@@ -70,27 +145,69 @@ extern "C" {
     static __text_end: u8;
 }
 
-/// Returns the (start address, end address) of the available memory on this
-/// system if it can be determined. If it cannot, `None` is returned.
+/// Returns the usable memory of this system if it can be determined. If it
+/// cannot, `None` is returned.
+///
+/// Memory regions are taken from the ATAGs or, when the firmware passed a
+/// device tree instead, from its `/memory` nodes. If neither is available, the
+/// ARM memory reported by the firmware over the mailbox is used. The firmware
+/// area and the kernel image, everything the firmware asks to be reserved, the
+/// VideoCore's share of RAM and the peripheral window are then carved out.
 ///
 /// This function is expected to return `Some` under all normal cirumstances.
-pub fn memory_map() -> Option<(usize, usize)> {
-    let page_size = 1 << 12;
+pub fn memory_map() -> Option<MemoryMap> {
     let binary_end = unsafe { (&__text_end as *const u8) as usize };
+    let mut map = MemoryMap::new();
 
     for tag in Atags::get() {
         if let Some(mem) = tag.mem() {
-            return Some(
-                (binary_end as usize, (binary_end + mem.size as usize) as usize)
-            );
+            let start = mem.start as usize;
+            map.add(Region::new(start, start + mem.size as usize));
+        }
+    }
+
+    if map.is_empty() {
+        if let Some(fdt) = DeviceTree::get() {
+            fdt.memory_regions(|start, size| {
+                map.add(Region::new(start as usize, (start + size) as usize));
+            });
+            fdt.reserved_regions(|start, size| {
+                map.reserve(Region::new(start as usize, (start + size) as usize));
+            });
+            map.reserve(Region::new(fdt.base(), fdt.base() + fdt.size()));
         }
     }
-    None
+
+    let arm_memory = crate::mailbox::with(mailbox::arm_memory).ok();
+    if map.is_empty() {
+        let (base, size) = arm_memory?;
+        map.add(Region::new(base, base + size));
+    }
+
+    if map.is_empty() {
+        return None;
+    }
+
+    // The firmware's spin tables and ATAGs live below the kernel image, and
+    // the boot stack grows down from the image's load address.
+    map.reserve(Region::new(0, binary_end));
+
+    // Everything between the end of ARM memory and the peripherals belongs to
+    // the VideoCore. The firmware knows where the split is; the boot
+    // information may describe the whole of RAM.
+    let arm_end = match arm_memory {
+        Some((base, size)) => base + size,
+        None => map.end(),
+    };
+    map.reserve(Region::new(arm_end, IO_BASE));
+    map.reserve(Region::new(IO_BASE, IO_BASE_END));
+
+    Some(map)
 }
 
 impl fmt::Debug for Allocator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.0.lock().as_mut() {
+        match self.inner.lock().as_mut() {
             Some(ref alloc) => write!(f, "{:?}", alloc)?,
             None => write!(f, "Not yet initialized")?,
         }
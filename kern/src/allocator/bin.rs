@@ -29,19 +29,25 @@ impl Allocator {
     /// Creates a new bin allocator that will allocate memory from the region
     /// starting at address `start` and ending at address `end`.
     pub fn new(start: usize, end: usize) -> Allocator {
-        let mut list = [LinkedList::new(); 32];
+        let mut allocator = Allocator {list: [LinkedList::new(); 32], allocated: 0, total: 0};
+        allocator.add_region(start, end);
+        allocator
+    }
+
+    /// Adds the region starting at address `start` and ending at address `end`
+    /// to the memory this allocator hands out. The region must not overlap
+    /// memory already owned by the allocator.
+    pub fn add_region(&mut self, start: usize, end: usize) {
         let mut current = start;
-        let mut total = 0;
         while current + size_of::<usize>() <= end {
             let small = current & (!current + 1);
             let size = min(small, 1 << (8*(size_of::<usize>())-(end-current).leading_zeros() as usize -1));
-            total += size;
+            self.total += size;
             unsafe {
-                list[size.trailing_zeros() as usize].push(current as *mut usize);
+                self.list[size.trailing_zeros() as usize].push(current as *mut usize);
             }
             current += size;
         }
-        Allocator {list: list, allocated: 0, total}
     }
 }
 
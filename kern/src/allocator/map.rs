@@ -0,0 +1,126 @@
+use core::fmt;
+
+/// The maximum number of discontiguous regions a `MemoryMap` can hold.
+pub const MAX_REGIONS: usize = 16;
+
+/// A range of physical memory `[start, end)`.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Region {
+    /// Returns the region `[start, end)`. An `end` below `start` yields an
+    /// empty region.
+    pub fn new(start: usize, end: usize) -> Region {
+        Region { start, end: end.max(start) }
+    }
+
+    /// Returns the size of the region in bytes.
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns `true` if the region contains no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl fmt::Debug for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:#010x}, {:#010x})", self.start, self.end)
+    }
+}
+
+/// The usable physical memory of the system: a sorted set of disjoint
+/// regions from which reserved ranges have been carved out.
+///
+/// The map is built before the heap exists, so it is backed by a fixed-size
+/// array.
+#[derive(Copy, Clone)]
+pub struct MemoryMap {
+    regions: [Region; MAX_REGIONS],
+    len: usize,
+}
+
+impl MemoryMap {
+    /// Returns an empty memory map.
+    pub fn new() -> MemoryMap {
+        MemoryMap {
+            regions: [Region { start: 0, end: 0 }; MAX_REGIONS],
+            len: 0,
+        }
+    }
+
+    /// Returns the usable regions, sorted by start address.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions[..self.len]
+    }
+
+    /// Returns `true` if there is no usable memory.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the end of the highest usable region, or `0` if there is none.
+    pub fn end(&self) -> usize {
+        self.regions().last().map(|r| r.end).unwrap_or(0)
+    }
+
+    /// Returns the total number of usable bytes.
+    pub fn total(&self) -> usize {
+        self.regions().iter().map(|r| r.size()).sum()
+    }
+
+    /// Adds `region` as usable memory. `region` must not overlap any region
+    /// already in the map.
+    ///
+    /// Returns `false` if the map is full and `region` had to be dropped.
+    pub fn add(&mut self, region: Region) -> bool {
+        if region.is_empty() {
+            return true;
+        }
+        if self.len == MAX_REGIONS {
+            return false;
+        }
+
+        let index = self.regions().iter().position(|r| r.start > region.start).unwrap_or(self.len);
+        for i in (index..self.len).rev() {
+            self.regions[i + 1] = self.regions[i];
+        }
+        self.regions[index] = region;
+        self.len += 1;
+        true
+    }
+
+    /// Removes `reserved` from the usable memory, splitting regions as needed.
+    ///
+    /// Returns `false` if a split did not fit in the map, in which case the
+    /// highest region is dropped.
+    pub fn reserve(&mut self, reserved: Region) -> bool {
+        if reserved.is_empty() {
+            return true;
+        }
+
+        let old = *self;
+        self.len = 0;
+        let mut fits = true;
+        for r in old.regions() {
+            if r.end <= reserved.start || reserved.end <= r.start {
+                fits &= self.add(*r);
+                continue;
+            }
+            fits &= self.add(Region::new(r.start, reserved.start));
+            fits &= self.add(Region::new(reserved.end, r.end));
+        }
+        fits
+    }
+}
+
+impl fmt::Debug for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.regions()).finish()
+    }
+}
@@ -0,0 +1,379 @@
+use core::alloc::Layout;
+use core::cmp::max;
+use core::fmt;
+use core::mem::size_of;
+use core::ptr;
+
+use crate::allocator::bin;
+use crate::allocator::linked_list::LinkedList;
+use crate::allocator::util::*;
+use crate::allocator::LocalAlloc;
+
+/// The size (and alignment) of a single slab.
+const SLAB_SIZE: usize = 1 << 14;
+
+/// Objects of `2^MIN_OBJECT_SHIFT` bytes are the smallest ones served by a
+/// cache. Smaller requests are rounded up.
+const MIN_OBJECT_SHIFT: usize = 4;
+
+/// Objects of `2^MAX_OBJECT_SHIFT` bytes are the largest ones served by a
+/// cache. Larger requests go straight to the backing allocator.
+const MAX_OBJECT_SHIFT: usize = 11;
+
+/// The number of per-size caches.
+pub const NUM_CACHES: usize = MAX_OBJECT_SHIFT - MIN_OBJECT_SHIFT + 1;
+
+/// The number of completely free slabs a cache keeps around. Any further empty
+/// slab is handed back to the backing allocator as soon as it becomes empty.
+const MAX_EMPTY_SLABS: usize = 1;
+
+/// The header placed at the start of every slab. The objects of the slab
+/// follow the header, each aligned to the object size of its cache.
+#[repr(C)]
+struct Slab {
+    prev: *mut Slab,
+    next: *mut Slab,
+    free: LinkedList,
+    in_use: usize,
+}
+
+impl Slab {
+    /// The layout requested from the backing allocator for a new slab.
+    fn layout() -> Layout {
+        unsafe { Layout::from_size_align_unchecked(SLAB_SIZE, SLAB_SIZE) }
+    }
+
+    /// Writes a new slab header at `base` and threads every object of
+    /// `object_size` bytes that fits in the slab onto its free list.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to `SLAB_SIZE` bytes of unused memory aligned to
+    /// `SLAB_SIZE`.
+    unsafe fn create(base: *mut u8, object_size: usize) -> *mut Slab {
+        let first = align_up(base as usize + size_of::<Slab>(), object_size);
+        let count = (base as usize + SLAB_SIZE - first) / object_size;
+
+        let mut free = LinkedList::new();
+        for i in (0..count).rev() {
+            free.push((first + i * object_size) as *mut usize);
+        }
+
+        let slab = base as *mut Slab;
+        ptr::write(slab, Slab {
+            prev: ptr::null_mut(),
+            next: ptr::null_mut(),
+            free,
+            in_use: 0,
+        });
+        slab
+    }
+
+    /// Returns the slab containing the object at `ptr`.
+    fn containing(ptr: *mut u8) -> *mut Slab {
+        align_down(ptr as usize, SLAB_SIZE) as *mut Slab
+    }
+}
+
+/// An intrusive, doubly linked list of slabs.
+struct SlabList {
+    head: *mut Slab,
+    len: usize,
+}
+
+unsafe impl Send for SlabList {}
+
+impl SlabList {
+    const fn new() -> SlabList {
+        SlabList {
+            head: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    /// Returns the first slab in the list without removing it, if any.
+    fn peek(&self) -> Option<*mut Slab> {
+        match self.head.is_null() {
+            true => None,
+            false => Some(self.head),
+        }
+    }
+
+    /// Pushes `slab` to the front of the list.
+    unsafe fn push(&mut self, slab: *mut Slab) {
+        (*slab).prev = ptr::null_mut();
+        (*slab).next = self.head;
+        if !self.head.is_null() {
+            (*self.head).prev = slab;
+        }
+        self.head = slab;
+        self.len += 1;
+    }
+
+    /// Unlinks `slab`, which must currently be in this list.
+    unsafe fn remove(&mut self, slab: *mut Slab) {
+        let (prev, next) = ((*slab).prev, (*slab).next);
+        if prev.is_null() {
+            self.head = next;
+        } else {
+            (*prev).next = next;
+        }
+        if !next.is_null() {
+            (*next).prev = prev;
+        }
+        self.len -= 1;
+    }
+
+    /// Removes and returns the first slab in the list, if any.
+    fn pop(&mut self) -> Option<*mut Slab> {
+        let slab = self.peek()?;
+        unsafe { self.remove(slab) };
+        Some(slab)
+    }
+}
+
+/// Statistics for a single object cache.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CacheStats {
+    /// The size of the objects served by the cache.
+    pub object_size: usize,
+    /// The number of objects currently handed out.
+    pub objects_in_use: usize,
+    /// The number of slabs currently owned by the cache.
+    pub slabs: usize,
+    /// The total number of allocations served by the cache.
+    pub allocs: usize,
+    /// The total number of deallocations returned to the cache.
+    pub frees: usize,
+    /// The total number of empty slabs returned to the backing allocator.
+    pub slabs_released: usize,
+}
+
+/// A cache of equally sized objects carved out of slabs.
+///
+/// Every slab is in exactly one of three lists: `partial` (some objects are
+/// free), `full` (no object is free) or `empty` (every object is free).
+struct Cache {
+    partial: SlabList,
+    full: SlabList,
+    empty: SlabList,
+    stats: CacheStats,
+}
+
+impl Cache {
+    fn new(object_size: usize) -> Cache {
+        Cache {
+            partial: SlabList::new(),
+            full: SlabList::new(),
+            empty: SlabList::new(),
+            stats: CacheStats {
+                object_size,
+                ..CacheStats::default()
+            },
+        }
+    }
+
+    /// Returns a slab with at least one free object, taking a new one from
+    /// `backing` if needed. Returns `None` if `backing` is exhausted.
+    unsafe fn slab<A: LocalAlloc>(&mut self, backing: &mut A) -> Option<*mut Slab> {
+        if let Some(slab) = self.partial.peek() {
+            return Some(slab);
+        }
+
+        let slab = match self.empty.pop() {
+            Some(slab) => slab,
+            None => {
+                let base = backing.alloc(Slab::layout());
+                if base.is_null() {
+                    return None;
+                }
+                self.stats.slabs += 1;
+                Slab::create(base, self.stats.object_size)
+            }
+        };
+        self.partial.push(slab);
+        Some(slab)
+    }
+
+    unsafe fn alloc<A: LocalAlloc>(&mut self, backing: &mut A) -> *mut u8 {
+        let slab = match self.slab(backing) {
+            Some(slab) => slab,
+            None => return ptr::null_mut(),
+        };
+
+        let object = (*slab).free.pop().expect("partial slab should have a free object");
+        (*slab).in_use += 1;
+        if (*slab).free.is_empty() {
+            self.partial.remove(slab);
+            self.full.push(slab);
+        }
+
+        self.stats.allocs += 1;
+        self.stats.objects_in_use += 1;
+        object as *mut u8
+    }
+
+    unsafe fn dealloc<A: LocalAlloc>(&mut self, ptr: *mut u8, backing: &mut A) {
+        let slab = Slab::containing(ptr);
+        if (*slab).free.is_empty() {
+            self.full.remove(slab);
+            self.partial.push(slab);
+        }
+        (*slab).free.push(ptr as *mut usize);
+        (*slab).in_use -= 1;
+        if (*slab).in_use == 0 {
+            self.partial.remove(slab);
+            self.empty.push(slab);
+        }
+
+        self.stats.frees += 1;
+        self.stats.objects_in_use -= 1;
+        while self.empty.len > MAX_EMPTY_SLABS {
+            self.release(backing);
+        }
+    }
+
+    /// Returns one empty slab to `backing`. Returns `false` if there was none.
+    unsafe fn release<A: LocalAlloc>(&mut self, backing: &mut A) -> bool {
+        match self.empty.pop() {
+            Some(slab) => {
+                backing.dealloc(slab as *mut u8, Slab::layout());
+                self.stats.slabs -= 1;
+                self.stats.slabs_released += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("stats", &self.stats)
+            .field("partial", &self.partial.len)
+            .field("full", &self.full.len)
+            .field("empty", &self.empty.len)
+            .finish()
+    }
+}
+
+/// A slab allocator layered on top of the bin allocator.
+///
+/// Requests of up to `2^MAX_OBJECT_SHIFT` bytes are served from per-size
+/// object caches, each of which carves `SLAB_SIZE` slabs out of the backing
+/// allocator. Everything else is forwarded to the backing allocator as is.
+pub struct Allocator {
+    backing: bin::Allocator,
+    caches: [Cache; NUM_CACHES],
+}
+
+impl Allocator {
+    /// Creates a new slab allocator that will allocate memory from the region
+    /// starting at address `start` and ending at address `end`.
+    pub fn new(start: usize, end: usize) -> Allocator {
+        Allocator {
+            backing: bin::Allocator::new(start, end),
+            caches: [
+                Cache::new(1 << 4),
+                Cache::new(1 << 5),
+                Cache::new(1 << 6),
+                Cache::new(1 << 7),
+                Cache::new(1 << 8),
+                Cache::new(1 << 9),
+                Cache::new(1 << 10),
+                Cache::new(1 << 11),
+            ],
+        }
+    }
+
+    /// Adds the region starting at address `start` and ending at address `end`
+    /// to the backing allocator.
+    pub fn add_region(&mut self, start: usize, end: usize) {
+        self.backing.add_region(start, end);
+    }
+
+    /// Returns the index of the cache serving `layout`, if any.
+    fn cache_index(layout: &Layout) -> Option<usize> {
+        let size = max(max(layout.size(), layout.align()), 1 << MIN_OBJECT_SHIFT);
+        let shift = size.next_power_of_two().trailing_zeros() as usize;
+        if shift > MAX_OBJECT_SHIFT {
+            None
+        } else {
+            Some(shift - MIN_OBJECT_SHIFT)
+        }
+    }
+
+    /// Returns every empty slab held by the caches to the backing allocator.
+    /// Returns the number of slabs released.
+    pub fn shrink(&mut self) -> usize {
+        let mut released = 0;
+        for cache in self.caches.iter_mut() {
+            while unsafe { cache.release(&mut self.backing) } {
+                released += 1;
+            }
+        }
+        released
+    }
+
+    /// Returns an iterator over the statistics of each object cache.
+    pub fn stats(&self) -> impl Iterator<Item = CacheStats> + '_ {
+        self.caches.iter().map(|cache| cache.stats)
+    }
+}
+
+impl LocalAlloc for Allocator {
+    /// Allocates memory. Returns a pointer meeting the size and alignment
+    /// properties of `layout.size()` and `layout.align()`.
+    ///
+    /// Small requests are served from the matching object cache; the rest are
+    /// forwarded to the backing allocator.
+    ///
+    /// # Safety
+    ///
+    /// The _caller_ must ensure that `layout.size() > 0` and that
+    /// `layout.align()` is a power of two. Parameters not meeting these
+    /// conditions may result in undefined behavior.
+    ///
+    /// # Errors
+    ///
+    /// Returning null pointer (`core::ptr::null_mut`)
+    /// indicates that either memory is exhausted
+    /// or `layout` does not meet this allocator's
+    /// size or alignment constraints.
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        match Self::cache_index(&layout) {
+            Some(index) => self.caches[index].alloc(&mut self.backing),
+            None => self.backing.alloc(layout),
+        }
+    }
+
+    /// Deallocates the memory referenced by `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// The _caller_ must ensure the following:
+    ///
+    ///   * `ptr` must denote a block of memory currently allocated via this
+    ///     allocator
+    ///   * `layout` must properly represent the original layout used in the
+    ///     allocation call that returned `ptr`
+    ///
+    /// Parameters not meeting these conditions may result in undefined
+    /// behavior.
+    unsafe fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
+        match Self::cache_index(&layout) {
+            Some(index) => self.caches[index].dealloc(ptr, &mut self.backing),
+            None => self.backing.dealloc(ptr, layout),
+        }
+    }
+}
+
+impl fmt::Debug for Allocator {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SlabAllocator")
+            .field("caches", &self.caches)
+            .field("backing", &self.backing)
+            .finish()
+    }
+}
@@ -0,0 +1,112 @@
+use core::alloc::Layout;
+use core::cmp::max;
+use core::fmt;
+use core::mem::size_of;
+
+/// The number of power-of-two size classes tracked, mirroring the bins of the
+/// bin allocator: class `k` counts requests in (2^(k-1), 2^k].
+pub const NUM_CLASSES: usize = 32;
+
+/// Counters for a single size class.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ClassStats {
+    /// The total number of successful allocations in this class.
+    pub allocs: usize,
+    /// The total number of deallocations in this class.
+    pub frees: usize,
+}
+
+impl ClassStats {
+    const fn new() -> ClassStats {
+        ClassStats { allocs: 0, frees: 0 }
+    }
+
+    /// Returns the number of allocations in this class that are still live.
+    pub fn live(&self) -> usize {
+        self.allocs - self.frees
+    }
+}
+
+/// Heap-wide allocation statistics.
+#[derive(Copy, Clone)]
+pub struct HeapStats {
+    classes: [ClassStats; NUM_CLASSES],
+    /// The number of requested bytes currently allocated.
+    pub bytes_in_use: usize,
+    /// The largest value `bytes_in_use` has ever reached.
+    pub high_water: usize,
+    /// The number of allocation requests that could not be satisfied.
+    pub failed: usize,
+}
+
+impl HeapStats {
+    /// Returns zeroed statistics.
+    pub const fn new() -> HeapStats {
+        HeapStats {
+            classes: [ClassStats::new(); NUM_CLASSES],
+            bytes_in_use: 0,
+            high_water: 0,
+            failed: 0,
+        }
+    }
+
+    /// Returns the size class of `layout`.
+    pub fn class(layout: &Layout) -> usize {
+        let size = max(
+            layout.size().next_power_of_two(),
+            max(layout.align(), size_of::<usize>()),
+        );
+        size.trailing_zeros() as usize
+    }
+
+    /// Records an allocation request for `layout`. `ok` is `false` if the
+    /// request could not be satisfied.
+    pub fn record_alloc(&mut self, layout: &Layout, ok: bool) {
+        if !ok {
+            self.failed += 1;
+            return;
+        }
+
+        self.classes[Self::class(layout)].allocs += 1;
+        self.bytes_in_use += layout.size();
+        self.high_water = max(self.high_water, self.bytes_in_use);
+    }
+
+    /// Records the deallocation of a block allocated with `layout`.
+    pub fn record_dealloc(&mut self, layout: &Layout) {
+        self.classes[Self::class(layout)].frees += 1;
+        self.bytes_in_use -= layout.size();
+    }
+
+    /// Returns the counters of the size class holding blocks of `2^class`
+    /// bytes.
+    pub fn class_stats(&self, class: usize) -> ClassStats {
+        self.classes[class]
+    }
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "in use: {} bytes", self.bytes_in_use)?;
+        writeln!(f, "high water: {} bytes", self.high_water)?;
+        writeln!(f, "failed allocations: {}", self.failed)?;
+        writeln!(f, "{:>10} {:>10} {:>10} {:>10}", "class", "allocs", "frees", "live")?;
+        for (class, stats) in self.classes.iter().enumerate() {
+            if stats.allocs != 0 {
+                writeln!(f, "{:>10} {:>10} {:>10} {:>10}",
+                    1usize << class, stats.allocs, stats.frees, stats.live())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HeapStats")
+            .field("bytes_in_use", &self.bytes_in_use)
+            .field("high_water", &self.high_water)
+            .field("failed", &self.failed)
+            .finish()
+    }
+}
@@ -76,7 +76,7 @@ mod allocator {
 
     use core::alloc::Layout;
 
-    use crate::allocator::{bin, bump, LocalAlloc};
+    use crate::allocator::{bin, bump, slab, LocalAlloc};
 
     macro_rules! test_allocators {
         (@$kind:ident, $name:ident, $mem:expr, |$info:pat| $block:expr) => {
@@ -289,6 +289,236 @@ mod allocator {
             }
         }
     });
+
+    test_allocators!(@slab, slab_alloc, 8 * (1 << 20), |(start, end, a)| {
+        let layouts = [
+            layout!(1, 1),
+            layout!(8, 8),
+            layout!(16, 16),
+            layout!(16, 128),
+            layout!(24, 8),
+            layout!(4, 256),
+            layout!(1024, 16),
+            layout!(2048, 8),
+            layout!(2049, 8),
+            layout!(4096, 4096),
+            layout!(16, 4096),
+            layout!(8192, 8),
+        ];
+
+        // Mix cached and forwarded sizes.
+        test_layouts!(layouts, start, end, a);
+    });
+
+    test_allocators!(@slab, slab_reuse, 1 << 20, |(_, _, mut a)| {
+        let layout = layout!(24, 8);
+
+        // objects freed back to a cache must be handed out again
+        let mut ptrs = vec![];
+        for _ in 0..100 {
+            let ptr = a.alloc(layout.clone());
+            assert!(!ptr.is_null());
+            scribble(ptr, layout.size());
+            ptrs.push(ptr);
+        }
+        for &ptr in &ptrs {
+            a.dealloc(ptr, layout.clone());
+        }
+
+        let ptr = a.alloc(layout.clone());
+        assert!(ptrs.contains(&ptr));
+        a.dealloc(ptr, layout.clone());
+
+        let stats = a.stats().nth(1).unwrap();
+        assert_eq!(stats.object_size, 32);
+        assert_eq!(stats.objects_in_use, 0);
+        assert_eq!(stats.allocs, 101);
+        assert_eq!(stats.frees, 101);
+        assert_eq!(stats.slabs, 1);
+    });
+
+    test_allocators!(@slab, slab_release, 1 << 20, |(_, _, mut a)| {
+        let layout = layout!(16, 16);
+
+        // fill a few slabs, then free everything: only one empty slab should
+        // be kept around, and `shrink` should hand that one back as well
+        let mut ptrs = vec![];
+        for _ in 0..3000 {
+            let ptr = a.alloc(layout.clone());
+            assert!(!ptr.is_null());
+            ptrs.push(ptr);
+        }
+        assert!(a.stats().next().unwrap().slabs >= 3);
+
+        for ptr in ptrs {
+            a.dealloc(ptr, layout.clone());
+        }
+
+        let stats = a.stats().next().unwrap();
+        assert_eq!(stats.objects_in_use, 0);
+        assert_eq!(stats.slabs, 1);
+        assert!(stats.slabs_released >= 2);
+
+        assert_eq!(a.shrink(), 1);
+        assert_eq!(a.stats().next().unwrap().slabs, 0);
+        assert_eq!(a.shrink(), 0);
+    });
+
+    test_allocators!(@slab, slab_exhausted, 1 << 12, |(_, _, mut a)| {
+        // a slab does not fit: small requests fail instead of panicking
+        assert!(a.alloc(layout!(16, 16)).is_null());
+    });
+}
+
+mod stats {
+    use core::alloc::Layout;
+
+    use crate::allocator::stats::HeapStats;
+
+    #[test]
+    fn counters() {
+        let mut stats = HeapStats::new();
+        let small = Layout::from_size_align(24, 8).unwrap();
+        let big = Layout::from_size_align(4096, 4096).unwrap();
+
+        stats.record_alloc(&small, true);
+        stats.record_alloc(&small, true);
+        stats.record_alloc(&big, true);
+        stats.record_alloc(&big, false);
+        assert_eq!(stats.bytes_in_use, 24 * 2 + 4096);
+        assert_eq!(stats.failed, 1);
+
+        stats.record_dealloc(&big);
+        stats.record_dealloc(&small);
+        assert_eq!(stats.bytes_in_use, 24);
+        assert_eq!(stats.high_water, 24 * 2 + 4096);
+
+        assert_eq!(HeapStats::class(&small), 5);
+        assert_eq!(stats.class_stats(5).allocs, 2);
+        assert_eq!(stats.class_stats(5).live(), 1);
+        assert_eq!(stats.class_stats(12).allocs, 1);
+        assert_eq!(stats.class_stats(12).live(), 0);
+    }
+}
+
+#[cfg(feature = "heap-trace")]
+mod trace {
+    use crate::allocator::trace::{is_allocator, Tracer, DEPTH};
+
+    const NO_FRAMES: [usize; DEPTH] = [0; DEPTH];
+
+    #[test]
+    fn insert_remove() {
+        let mut tracer = Tracer::new();
+        let ptrs: Vec<usize> = (1..2000).map(|i| i * 16).collect();
+        for &ptr in &ptrs {
+            tracer.insert(ptr as *mut u8, ptr / 16, NO_FRAMES);
+        }
+
+        // removing records must not hide those that probed past them
+        for &ptr in ptrs.iter().step_by(3) {
+            tracer.remove(ptr as *mut u8);
+        }
+        for (i, &ptr) in ptrs.iter().enumerate() {
+            let expected = if i % 3 == 0 { None } else { Some(ptr / 16) };
+            assert_eq!(tracer.size_of(ptr), expected);
+        }
+        assert_eq!(tracer.untraced(), 0);
+    }
+
+    #[test]
+    fn full() {
+        let mut tracer = Tracer::new();
+        for i in 1..=4096 {
+            tracer.insert((i * 32) as *mut u8, 32, NO_FRAMES);
+        }
+        // a quarter of the table is kept empty
+        assert_eq!(tracer.untraced(), 1024);
+        assert_eq!(tracer.size_of(32), Some(32));
+
+        tracer.remove((4096 * 32) as *mut u8);
+        assert_eq!(tracer.untraced(), 1023);
+        tracer.remove(32 as *mut u8);
+        assert_eq!(tracer.size_of(32), None);
+        tracer.insert(32 as *mut u8, 64, NO_FRAMES);
+        assert_eq!(tracer.size_of(32), Some(64));
+    }
+
+    #[test]
+    fn allocator_symbols() {
+        assert!(is_allocator("__rust_alloc"));
+        assert!(is_allocator("__rg_alloc"));
+        assert!(is_allocator("_ZN5alloc5alloc15exchange_malloc17h0123456789abcdefE"));
+        assert!(is_allocator("_ZN4core3ptr13drop_in_place17h0123456789abcdefE"));
+        assert!(is_allocator("_ZN6kernel9allocator4slab9Allocator5alloc17h0123456789abcdefE"));
+        assert!(is_allocator(
+            "_ZN73_$LT$kernel..allocator..Allocator$u20$as$u20$core..alloc..GlobalAlloc$GT$5alloc17h0123456789abcdefE"));
+        assert!(is_allocator(
+            "_ZN63_$LT$alloc..vec..Vec$LT$T$GT$$u20$as$u20$core..clone..Clone$GT$5clone17h0123456789abcdefE"));
+
+        assert!(!is_allocator("_ZN6kernel5shell5shell17h0123456789abcdefE"));
+        assert!(!is_allocator("_ZN6kernel2fs10FileSystem10initialize17h0123456789abcdefE"));
+        assert!(!is_allocator("_ZN9allocator3foo17h0123456789abcdefE"));
+        assert!(!is_allocator("_ZN9alloc_foo3bar17h0123456789abcdefE"));
+        assert!(!is_allocator(
+            "_ZN61_$LT$kernel..fs..FileSystem$u20$as$u20$core..clone..Clone$GT$5clone17h0123456789abcdefE"));
+        assert!(!is_allocator("_start"));
+    }
+}
+
+mod memory_map {
+    use crate::allocator::map::{MemoryMap, Region, MAX_REGIONS};
+
+    fn regions(map: &MemoryMap) -> Vec<(usize, usize)> {
+        map.regions().iter().map(|r| (r.start, r.end)).collect()
+    }
+
+    #[test]
+    fn add_sorted() {
+        let mut map = MemoryMap::new();
+        assert!(map.is_empty());
+        assert!(map.add(Region::new(0x4000, 0x5000)));
+        assert!(map.add(Region::new(0x0, 0x1000)));
+        assert!(map.add(Region::new(0x2000, 0x2000)));
+        assert_eq!(regions(&map), vec![(0x0, 0x1000), (0x4000, 0x5000)]);
+        assert_eq!(map.end(), 0x5000);
+        assert_eq!(map.total(), 0x2000);
+    }
+
+    #[test]
+    fn reserve() {
+        let mut map = MemoryMap::new();
+        map.add(Region::new(0x0, 0x10000));
+        map.add(Region::new(0x20000, 0x30000));
+
+        // split in the middle
+        assert!(map.reserve(Region::new(0x4000, 0x5000)));
+        assert_eq!(regions(&map), vec![(0x0, 0x4000), (0x5000, 0x10000), (0x20000, 0x30000)]);
+
+        // trim the start of one region and the end of another
+        assert!(map.reserve(Region::new(0x0, 0x1000)));
+        assert!(map.reserve(Region::new(0x2c000, 0x40000)));
+        assert_eq!(regions(&map), vec![(0x1000, 0x4000), (0x5000, 0x10000), (0x20000, 0x2c000)]);
+
+        // drop a region entirely, across a hole
+        assert!(map.reserve(Region::new(0x4000, 0x1c000)));
+        assert_eq!(regions(&map), vec![(0x1000, 0x4000), (0x20000, 0x2c000)]);
+
+        // reserving outside of usable memory is a no-op
+        assert!(map.reserve(Region::new(0x80000, 0x90000)));
+        assert_eq!(map.total(), 0x3000 + 0xc000);
+    }
+
+    #[test]
+    fn full() {
+        let mut map = MemoryMap::new();
+        for i in 0..MAX_REGIONS {
+            assert!(map.add(Region::new(i * 0x100, i * 0x100 + 0x80)));
+        }
+        assert!(!map.add(Region::new(0x10000, 0x20000)));
+        assert!(!map.reserve(Region::new(0x10, 0x20)));
+        assert_eq!(map.regions().len(), MAX_REGIONS);
+    }
 }
 
 mod linked_list {
@@ -0,0 +1,268 @@
+#[cfg(not(test))]
+use pi::common::IO_BASE;
+
+use crate::ksyms;
+
+/// The number of return addresses recorded per allocation, innermost first.
+/// Enough to get past the allocator and the `alloc` crate to the call site.
+pub const DEPTH: usize = 6;
+
+/// log2 of the number of records of the table.
+const RECORD_BITS: u32 = 12;
+
+/// The number of records of the table.
+const MAX_RECORDS: usize = 1 << RECORD_BITS;
+
+/// The maximum number of live allocations that can be traced at once. The
+/// table is kept partly empty so that probe sequences stay short.
+const MAX_LIVE: usize = MAX_RECORDS / 4 * 3;
+
+/// The maximum number of distinct call sites reported by
+/// `Tracer::for_each_site()`. Any further site is accounted under `OTHER`.
+const MAX_SITES: usize = 32;
+
+/// The call site live allocations are reported under once `MAX_SITES` is
+/// reached, or when none of their return addresses is past the allocator.
+pub const OTHER: usize = 0;
+
+#[derive(Copy, Clone)]
+struct Record {
+    ptr: usize,
+    size: usize,
+    frames: [usize; DEPTH],
+}
+
+impl Record {
+    const EMPTY: Record = Record { ptr: 0, size: 0, frames: [0; DEPTH] };
+}
+
+/// Returns the slot where the search for the record of `ptr` starts.
+fn home(ptr: usize) -> usize {
+    // Fibonacci hashing: the low bits of `ptr` are mostly alignment.
+    ((ptr as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - RECORD_BITS)) as usize
+}
+
+/// Returns the return addresses of the innermost `DEPTH` frames of the
+/// caller, innermost first, by walking the frame-pointer chain as the panic
+/// handler does. Missing frames are left zero.
+#[cfg(not(test))]
+#[inline(always)]
+pub fn call_stack() -> [usize; DEPTH] {
+    let mut frames = [0; DEPTH];
+    let mut fp: usize;
+    unsafe { asm!("mov $0, x29" : "=r"(fp) ::: "volatile") };
+    for frame in frames.iter_mut() {
+        if fp == 0 || fp % 8 != 0 || fp >= IO_BASE {
+            break;
+        }
+        let (next, lr) = unsafe { (*(fp as *const usize), *((fp + 8) as *const usize)) };
+        if lr < 4 {
+            break;
+        }
+        *frame = lr - 4;
+        fp = next;
+    }
+    frames
+}
+
+/// Host builds have no kernel frames to walk.
+#[cfg(test)]
+pub fn call_stack() -> [usize; DEPTH] {
+    [0; DEPTH]
+}
+
+/// Returns `true` if `path`, a path of a mangled name, is in the crate (or
+/// module) `krate`.
+fn in_crate(path: &str, krate: &str) -> bool {
+    path.starts_with(krate) && (path.len() == krate.len() || path[krate.len()..].starts_with(".."))
+}
+
+/// Returns `true` if `name`, a mangled name, is that of a function that
+/// allocations are requested through: one of the allocator, or of the `alloc`
+/// and `core` crates.
+pub(super) fn is_allocator(name: &str) -> bool {
+    if name.starts_with("__rust_") || name.starts_with("__rg_") {
+        return true;
+    }
+    if !name.starts_with("_ZN") {
+        return false;
+    }
+
+    // The length of the first identifier, then the identifier: a crate name,
+    // or `_$LT$Type$u20$as$u20$Trait$GT$` for a trait implementation.
+    let path = &name[3..];
+    let digits = path.bytes().take_while(u8::is_ascii_digit).count();
+    let ident = match path[..digits].parse::<usize>() {
+        Ok(len) => match path.get(digits..digits + len) {
+            Some(ident) => ident,
+            None => return false,
+        },
+        Err(_) => return false,
+    };
+    let rest = &path[digits + ident.len()..];
+    let ident = ident.trim_start_matches("_$LT$");
+
+    in_crate(ident, "alloc") || in_crate(ident, "core")
+        || in_crate(ident, "kernel..allocator")
+        || (ident == "kernel" && rest.starts_with("9allocator"))
+}
+
+/// Remembers which return addresses are in the allocator (see
+/// `is_allocator()`), since call paths to it repeat and symbols are looked
+/// up linearly.
+struct SymbolCache {
+    entries: [(usize, bool); 256],
+}
+
+impl SymbolCache {
+    fn new() -> SymbolCache {
+        SymbolCache { entries: [(0, false); 256] }
+    }
+
+    fn is_allocator(&mut self, pc: usize) -> bool {
+        let entry = &mut self.entries[(pc >> 2) % 256];
+        if entry.0 != pc {
+            let allocator = ksyms::lookup(pc).map_or(false, |(name, _)| is_allocator(name));
+            *entry = (pc, allocator);
+        }
+        entry.1
+    }
+
+    /// Returns the call site of the allocation made through `frames`: the
+    /// innermost return address past the allocator, or `OTHER`.
+    fn call_site(&mut self, frames: &[usize]) -> usize {
+        frames.iter()
+            .cloned()
+            .take_while(|&pc| pc != 0)
+            .find(|&pc| !self.is_allocator(pc))
+            .unwrap_or(OTHER)
+    }
+}
+
+/// The live allocations made from a call site.
+#[derive(Debug, Copy, Clone)]
+pub struct SiteSummary {
+    /// The address of the call, or `OTHER`.
+    pub site: usize,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// A fixed-size table of live allocations and the return addresses they
+/// were made through, indexed by address: a hash table with linear probing.
+///
+/// The table cannot grow since it is updated from within the allocator.
+/// Allocations made while it is full are counted but not traced.
+pub struct Tracer {
+    records: [Record; MAX_RECORDS],
+    live: usize,
+    untraced: usize,
+}
+
+impl Tracer {
+    /// Returns an empty tracer.
+    pub const fn new() -> Tracer {
+        Tracer {
+            records: [Record::EMPTY; MAX_RECORDS],
+            live: 0,
+            untraced: 0,
+        }
+    }
+
+    /// Records a new live allocation of `size` bytes at `ptr`, made through
+    /// the return addresses `frames`, innermost first.
+    pub fn insert(&mut self, ptr: *mut u8, size: usize, frames: [usize; DEPTH]) {
+        if self.live == MAX_LIVE {
+            self.untraced += 1;
+            return;
+        }
+        let mut i = home(ptr as usize);
+        while self.records[i].ptr != 0 {
+            i = (i + 1) % MAX_RECORDS;
+        }
+        self.records[i] = Record { ptr: ptr as usize, size, frames };
+        self.live += 1;
+    }
+
+    /// Forgets the allocation at `ptr`.
+    pub fn remove(&mut self, ptr: *mut u8) {
+        let mut hole = home(ptr as usize);
+        loop {
+            match self.records[hole].ptr {
+                0 => {
+                    self.untraced = self.untraced.saturating_sub(1);
+                    return;
+                }
+                p if p == ptr as usize => break,
+                _ => hole = (hole + 1) % MAX_RECORDS,
+            }
+        }
+        self.live -= 1;
+
+        // Move back the records that probed past the hole, so that searches
+        // do not stop at it.
+        let mut i = hole;
+        loop {
+            i = (i + 1) % MAX_RECORDS;
+            let record = self.records[i];
+            if record.ptr == 0 {
+                break;
+            }
+            let distance = i.wrapping_sub(home(record.ptr)) % MAX_RECORDS;
+            if distance >= i.wrapping_sub(hole) % MAX_RECORDS {
+                self.records[hole] = record;
+                hole = i;
+            }
+        }
+        self.records[hole] = Record::EMPTY;
+    }
+
+    /// Returns the number of live allocations that could not be traced.
+    pub fn untraced(&self) -> usize {
+        self.untraced
+    }
+
+    /// Calls `f` with a summary of the live allocations of each call site.
+    pub fn for_each_site<F: FnMut(&SiteSummary)>(&self, mut f: F) {
+        let empty = SiteSummary { site: OTHER, count: 0, bytes: 0 };
+        let mut sites = [empty; MAX_SITES];
+        let mut len = 0;
+        let mut symbols = SymbolCache::new();
+
+        for record in self.records.iter().filter(|r| r.ptr != 0) {
+            let site = symbols.call_site(&record.frames);
+            let index = match sites[..len].iter().position(|s| s.site == site) {
+                Some(index) => index,
+                None if site != OTHER && len < MAX_SITES - 1 => {
+                    sites[len].site = site;
+                    len += 1;
+                    len - 1
+                }
+                None => MAX_SITES - 1,
+            };
+            sites[index].count += 1;
+            sites[index].bytes += record.size;
+        }
+
+        for summary in sites[..len].iter().chain(sites[MAX_SITES - 1..].iter()) {
+            if summary.count != 0 {
+                f(summary);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Tracer {
+    /// Returns the size of the live allocation at `ptr`, if it is traced.
+    pub fn size_of(&self, ptr: usize) -> Option<usize> {
+        let mut i = home(ptr);
+        while self.records[i].ptr != 0 {
+            if self.records[i].ptr == ptr {
+                return Some(self.records[i].size);
+            }
+            i = (i + 1) % MAX_RECORDS;
+        }
+        None
+    }
+}
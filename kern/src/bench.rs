@@ -0,0 +1,81 @@
+use core::ptr;
+use core::time::Duration;
+
+use aarch64::*;
+use pi::timer::current_time;
+
+use crate::param::{PAGE_SIZE, USER_IMG_BASE};
+use crate::vm::asid::{self, Asid};
+use crate::vm::{PagePerm, UserPageTable, VirtualAddr};
+
+/// The number of pages each address space of the context switch benchmark
+/// touches after being switched to.
+const PAGES: usize = 16;
+
+/// The result of `context_switch()`.
+#[derive(Debug, Copy, Clone)]
+pub struct SwitchCost {
+    /// The number of address space switches measured.
+    pub switches: usize,
+    /// The time taken when flushing the TLB on every switch.
+    pub flushed: Duration,
+    /// The time taken when tagging both address spaces with an ASID.
+    pub tagged: Duration,
+}
+
+/// Measures the cost of switching between two user address spaces
+/// `switches` times, first by flushing the whole TLB on every switch (as
+/// done for ASID 0) and then by tagging both address spaces with an ASID.
+///
+/// After each switch, one word of each of the `PAGES` pages of the address
+/// space is read, so that the cost of refilling the TLB is accounted for.
+///
+/// The caller's `TTBR1_EL1` is restored before returning.
+pub fn context_switch(switches: usize) -> SwitchCost {
+    let mut tables = [UserPageTable::new(), UserPageTable::new()];
+    for table in tables.iter_mut() {
+        for i in 0..PAGES {
+            table.alloc(VirtualAddr::from(USER_IMG_BASE + i * PAGE_SIZE), PagePerm::RW);
+        }
+    }
+
+    let saved = unsafe { TTBR1_EL1.get() };
+
+    let untagged = [tables[0].get_baddr().as_u64(), tables[1].get_baddr().as_u64()];
+    let flushed = run(&untagged, switches, true);
+
+    let mut asids = [Asid::default(), Asid::default()];
+    let tagged = [
+        asid::ttbr(&mut asids[0], tables[0].get_baddr()),
+        asid::ttbr(&mut asids[1], tables[1].get_baddr()),
+    ];
+    let tagged = run(&tagged, switches, false);
+
+    unsafe {
+        TTBR1_EL1.set(saved);
+        isb();
+        tlbi_vmalle1();
+    }
+
+    SwitchCost { switches, flushed, tagged }
+}
+
+/// Alternates `TTBR1_EL1` between `ttbrs` `switches` times, touching every
+/// page after each switch. Returns the time taken.
+fn run(ttbrs: &[u64; 2], switches: usize, flush: bool) -> Duration {
+    let start = current_time();
+    for i in 0..switches {
+        unsafe {
+            TTBR1_EL1.set(ttbrs[i % 2]);
+            isb();
+            if flush {
+                tlbi_vmalle1();
+            }
+
+            for page in 0..PAGES {
+                ptr::read_volatile((USER_IMG_BASE + page * PAGE_SIZE) as *const u64);
+            }
+        }
+    }
+    current_time() - start
+}
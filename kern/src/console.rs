@@ -1,12 +1,20 @@
 use core::fmt;
-use pi::uart::MiniUart;
 use shim::io;
 
 use crate::mutex::Mutex;
 
+pub mod fb;
+pub mod rx;
+mod uart;
+
+pub use self::uart::Uart;
+
 /// A global singleton allowing read/write access to the console.
+///
+/// Everything written to the console is mirrored to the framebuffer console
+/// once it is initialized (see `fb::initialize()`).
 pub struct Console {
-    inner: Option<MiniUart>,
+    inner: Option<Uart>,
 }
 
 impl Console {
@@ -18,12 +26,12 @@ impl Console {
     /// Initializes the console if it's not already initialized.
     #[inline]
     fn initialize(&mut self) {
-        self.inner = Some(MiniUart::new());
+        self.inner = Some(Uart::new());
     }
 
-    /// Returns a mutable borrow to the inner `MiniUart`, initializing it as
+    /// Returns a mutable borrow to the inner `Uart`, initializing it as
     /// needed.
-    fn inner(&mut self) -> &mut MiniUart {
+    fn inner(&mut self) -> &mut Uart {
         if self.inner.is_none() {
             self.initialize();
         }
@@ -31,25 +39,52 @@ impl Console {
     }
 
     /// Reads a byte from the UART device, blocking until a byte is available.
+    ///
+    /// Once interrupt-driven reception is enabled (see `rx::initialize()`),
+    /// the byte is taken from the receive buffer instead. Either way, the
+    /// console is held until a byte arrives: prefer `rx::read_byte()`, which
+    /// releases it between polls.
     pub fn read_byte(&mut self) -> u8 {
+        if rx::is_enabled() {
+            return rx::poll_byte_from(self.inner());
+        }
         self.inner().read_byte()
     }
 
     /// Writes the byte `byte` to the UART device.
     pub fn write_byte(&mut self, byte: u8) {
         self.inner().write_byte(byte);
+        fb::mirror(&[byte]);
     }
 }
 
 impl io::Read for Console {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner().read(buf)
+        if !rx::is_enabled() {
+            return self.inner().read(buf);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = rx::poll_byte_from(self.inner());
+        let mut read = 1;
+        while read < buf.len() {
+            match rx::try_read_byte_from(self.inner()) {
+                Some(byte) => buf[read] = byte,
+                None => break,
+            }
+            read += 1;
+        }
+        Ok(read)
     }
 }
 
 impl io::Write for Console {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner().write(buf)
+        let written = self.inner().write(buf)?;
+        fb::mirror(&buf[..written]);
+        Ok(written)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -59,13 +94,28 @@ impl io::Write for Console {
 
 impl fmt::Write for Console {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.inner().write_str(s)
+        self.inner().write_str(s)?;
+        fb::mirror(s.as_bytes());
+        Ok(())
     }
 }
 
 /// Global `Console` singleton.
 pub static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
 
+/// Releases the console locks, whoever holds them, so that the panic handler
+/// can print: the panicking core may be holding them, or another core may
+/// never release them.
+///
+/// # Safety
+///
+/// Only the panic handler may call this: output of other cores in progress
+/// may interleave with its own.
+pub unsafe fn force_unlock() {
+    CONSOLE.force_unlock();
+    fb::force_unlock();
+}
+
 /// Internal function called by the `kprint[ln]!` macros.
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
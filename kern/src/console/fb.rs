@@ -0,0 +1,232 @@
+use core::fmt;
+
+use pi::framebuffer::{font, Color, Framebuffer};
+
+use crate::mutex::Mutex;
+
+/// The resolution requested for the framebuffer console.
+const WIDTH: usize = 1024;
+const HEIGHT: usize = 768;
+
+/// The ANSI colors, indexed by SGR color number (30-37 and 40-47), followed
+/// by their bright variants.
+const PALETTE: [Color; 16] = [
+    Color::rgb(0x00, 0x00, 0x00),
+    Color::rgb(0xaa, 0x00, 0x00),
+    Color::rgb(0x00, 0xaa, 0x00),
+    Color::rgb(0xaa, 0x55, 0x00),
+    Color::rgb(0x00, 0x00, 0xaa),
+    Color::rgb(0xaa, 0x00, 0xaa),
+    Color::rgb(0x00, 0xaa, 0xaa),
+    Color::rgb(0xaa, 0xaa, 0xaa),
+    Color::rgb(0x55, 0x55, 0x55),
+    Color::rgb(0xff, 0x55, 0x55),
+    Color::rgb(0x55, 0xff, 0x55),
+    Color::rgb(0xff, 0xff, 0x55),
+    Color::rgb(0x55, 0x55, 0xff),
+    Color::rgb(0xff, 0x55, 0xff),
+    Color::rgb(0x55, 0xff, 0xff),
+    Color::rgb(0xff, 0xff, 0xff),
+];
+
+const DEFAULT_FG: usize = 7;
+const DEFAULT_BG: usize = 0;
+
+/// The maximum number of parameters of an escape sequence.
+const MAX_PARAMS: usize = 4;
+
+/// The state of the escape sequence parser.
+#[derive(Copy, Clone, PartialEq)]
+enum Escape {
+    None,
+    /// After `ESC`.
+    Start,
+    /// After `ESC [`, with the number of parameters seen so far.
+    Csi(usize),
+}
+
+/// A text console drawn on a framebuffer.
+///
+/// The console understands `\n`, `\r`, `\t`, backspace and the ANSI escape
+/// sequences for colors (`ESC [ ... m`), clearing the screen (`ESC [ 2 J`) and
+/// moving the cursor home (`ESC [ H`). It scrolls when the cursor moves past
+/// the last row.
+pub struct TextConsole {
+    fb: Framebuffer,
+    cols: usize,
+    rows: usize,
+    col: usize,
+    row: usize,
+    fg: usize,
+    bg: usize,
+    bold: bool,
+    escape: Escape,
+    params: [usize; MAX_PARAMS],
+}
+
+impl TextConsole {
+    /// Returns a cleared text console covering `fb`.
+    pub fn new(fb: Framebuffer) -> TextConsole {
+        let mut console = TextConsole {
+            cols: fb.width() / font::WIDTH,
+            rows: fb.height() / font::HEIGHT,
+            fb,
+            col: 0,
+            row: 0,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            escape: Escape::None,
+            params: [0; MAX_PARAMS],
+        };
+        console.clear();
+        console
+    }
+
+    fn fg(&self) -> Color {
+        PALETTE[self.fg + if self.bold { 8 } else { 0 }]
+    }
+
+    fn bg(&self) -> Color {
+        PALETTE[self.bg]
+    }
+
+    /// Clears the screen and moves the cursor to the top left corner.
+    pub fn clear(&mut self) {
+        let (width, height, bg) = (self.fb.width(), self.fb.height(), self.bg());
+        self.fb.fill_rect(0, 0, width, height, bg);
+        self.col = 0;
+        self.row = 0;
+    }
+
+    /// Moves the cursor to the start of the next line, scrolling if needed.
+    fn newline(&mut self) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            let bg = self.bg();
+            self.fb.scroll_up(font::HEIGHT, bg);
+        }
+    }
+
+    /// Draws `byte` at the cursor and advances it.
+    fn put(&mut self, byte: u8) {
+        if self.col == self.cols {
+            self.newline();
+        }
+        let (fg, bg) = (self.fg(), self.bg());
+        self.fb.draw_char(self.col * font::WIDTH, self.row * font::HEIGHT, byte, fg, bg);
+        self.col += 1;
+    }
+
+    /// Applies the SGR (Select Graphic Rendition) parameter `param`.
+    fn set_graphic_rendition(&mut self, param: usize) {
+        match param {
+            0 => {
+                self.fg = DEFAULT_FG;
+                self.bg = DEFAULT_BG;
+                self.bold = false;
+            }
+            1 => self.bold = true,
+            22 => self.bold = false,
+            30..=37 => self.fg = param - 30,
+            39 => self.fg = DEFAULT_FG,
+            40..=47 => self.bg = param - 40,
+            49 => self.bg = DEFAULT_BG,
+            _ => {}
+        }
+    }
+
+    /// Feeds `byte`, the `count`-th parameter byte so far, to the escape
+    /// sequence parser.
+    fn csi(&mut self, byte: u8, count: usize) {
+        match byte {
+            b'0'..=b'9' => {
+                if count < MAX_PARAMS {
+                    self.params[count] = self.params[count] * 10 + (byte - b'0') as usize;
+                }
+            }
+            b';' => self.escape = Escape::Csi(count + 1),
+            b'm' => {
+                for i in 0..(count + 1).min(MAX_PARAMS) {
+                    self.set_graphic_rendition(self.params[i]);
+                }
+                self.escape = Escape::None;
+            }
+            b'J' => {
+                if self.params[0] == 2 {
+                    self.clear();
+                }
+                self.escape = Escape::None;
+            }
+            b'H' => {
+                self.col = 0;
+                self.row = 0;
+                self.escape = Escape::None;
+            }
+            // Unsupported sequence: ignore it.
+            _ => self.escape = Escape::None,
+        }
+    }
+
+    /// Writes `byte` to the console.
+    pub fn write_byte(&mut self, byte: u8) {
+        match (self.escape, byte) {
+            (Escape::None, 0x1b) => self.escape = Escape::Start,
+            (Escape::None, b'\n') => self.newline(),
+            (Escape::None, b'\r') => self.col = 0,
+            (Escape::None, b'\t') => {
+                for _ in 0..(8 - self.col % 8) {
+                    self.put(b' ');
+                }
+            }
+            (Escape::None, 0x08) => self.col = self.col.saturating_sub(1),
+            (Escape::None, 0x07) => {}
+            (Escape::None, byte) => self.put(byte),
+            (Escape::Start, b'[') => {
+                self.params = [0; MAX_PARAMS];
+                self.escape = Escape::Csi(0);
+            }
+            (Escape::Start, _) => self.escape = Escape::None,
+            (Escape::Csi(count), byte) => self.csi(byte, count),
+        }
+    }
+}
+
+impl fmt::Write for TextConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+/// The framebuffer console, once initialized.
+static FB_CONSOLE: Mutex<Option<TextConsole>> = Mutex::new(None);
+
+/// Allocates a framebuffer and starts mirroring the console to it. Returns
+/// an error if the firmware did not provide a framebuffer.
+///
+/// The caller should assure that the VideoCore memory is mapped before
+/// calling this function.
+pub fn initialize() -> Result<(), pi::mailbox::Error> {
+    let fb = crate::mailbox::with(|| Framebuffer::new(WIDTH, HEIGHT))?;
+    *FB_CONSOLE.lock() = Some(TextConsole::new(fb));
+    Ok(())
+}
+
+/// Releases the lock of the framebuffer console. See `console::force_unlock()`.
+pub(super) unsafe fn force_unlock() {
+    FB_CONSOLE.force_unlock();
+}
+
+/// Writes `bytes` to the framebuffer console, if it is initialized.
+pub fn mirror(bytes: &[u8]) {
+    if let Some(console) = FB_CONSOLE.lock().as_mut() {
+        for &byte in bytes {
+            console.write_byte(byte);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use alloc::boxed::Box;
+use core::sync::atomic::spin_loop_hint;
+
+use aarch64::*;
+use pi::interrupt::Controller;
+
+use crate::console::{Uart, CONSOLE};
+use crate::mutex::Mutex;
+use crate::IRQ;
+
+/// The number of received bytes buffered before further bytes are dropped.
+const RX_BUFFER_SIZE: usize = 4096;
+
+/// A fixed-size FIFO of bytes.
+struct RingBuffer {
+    buf: [u8; RX_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    const fn new() -> RingBuffer {
+        RingBuffer {
+            buf: [0; RX_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Appends `byte`. Returns `false` if the buffer is full, in which case
+    /// `byte` is dropped.
+    fn push(&mut self, byte: u8) -> bool {
+        if self.len == RX_BUFFER_SIZE {
+            return false;
+        }
+        self.buf[(self.head + self.len) % RX_BUFFER_SIZE] = byte;
+        self.len += 1;
+        true
+    }
+
+    /// Removes and returns the oldest byte, if any.
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % RX_BUFFER_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+/// The console's receive path once interrupts are enabled: the bytes drained
+/// from the console UART's receive FIFO so far.
+struct Receiver {
+    enabled: bool,
+    buffer: RingBuffer,
+}
+
+static RX: Mutex<Receiver> = Mutex::new(Receiver {
+    enabled: false,
+    buffer: RingBuffer::new(),
+});
+
+/// Moves every byte in the receive FIFO of `uart`, the console's, to the
+/// ring buffer. Bytes that do not fit are dropped.
+fn drain(uart: &mut Uart) {
+    let mut rx = RX.lock_irq();
+    while uart.has_byte() {
+        rx.buffer.push(uart.read_byte());
+    }
+}
+
+/// Switches the console to interrupt-driven reception: received bytes are
+/// moved to a ring buffer by the `Uart::interrupt()` handler, even while the
+/// console is not being read.
+///
+/// The caller should assure that `IRQ.initialize()` has been called before
+/// calling this function.
+pub fn initialize() {
+    CONSOLE.lock_irq().inner().set_rx_interrupt(true);
+    RX.lock_irq().enabled = true;
+
+    // When the console is busy, its holder drains the FIFO if it is reading;
+    // otherwise the interrupt is taken again once the console is released.
+    IRQ.register(Uart::interrupt(), Box::new(|_| {
+        if let Some(mut console) = CONSOLE.try_lock() {
+            drain(console.inner());
+        }
+    }));
+    Controller::new().enable(Uart::interrupt());
+}
+
+/// Returns `true` if `initialize()` has been called.
+pub fn is_enabled() -> bool {
+    RX.lock_irq().enabled
+}
+
+/// Returns the oldest received byte, if any, draining `uart`, the console's,
+/// first. Does not block.
+pub(super) fn try_read_byte_from(uart: &mut Uart) -> Option<u8> {
+    // Bytes are drained here too so that readers running with IRQs masked
+    // (e.g. from an exception handler) still make progress.
+    drain(uart);
+    RX.lock_irq().buffer.pop()
+}
+
+/// Returns the oldest received byte, draining `uart`, the console's, until
+/// one arrives. The core does not sleep: the caller holds the console, which
+/// other cores are waiting on.
+pub(super) fn poll_byte_from(uart: &mut Uart) -> u8 {
+    loop {
+        if let Some(byte) = try_read_byte_from(uart) {
+            return byte;
+        }
+        spin_loop_hint();
+    }
+}
+
+/// Returns the oldest received byte, if any. Does not block.
+pub fn try_read_byte() -> Option<u8> {
+    // IRQs are masked so that the UART handler cannot preempt the holder of
+    // `CONSOLE` on this core.
+    try_read_byte_from(CONSOLE.lock_irq().inner())
+}
+
+/// Returns `true` if a received byte is waiting to be read. Does not block,
+/// and may be used as the condition of `thread::wait_until()`: the console is
+/// only drained if it is not busy.
+pub fn has_byte() -> bool {
+    if let Some(mut console) = CONSOLE.try_lock_irq() {
+        drain(console.inner());
+    }
+    RX.lock_irq().buffer.len > 0
+}
+
+/// Returns the oldest received byte, sleeping until one arrives. The console
+/// is not held while sleeping.
+pub fn read_byte() -> u8 {
+    loop {
+        if let Some(byte) = try_read_byte() {
+            return byte;
+        }
+        // A pending UART interrupt wakes the core up even if IRQs are masked.
+        wfi();
+    }
+}
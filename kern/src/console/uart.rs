@@ -0,0 +1,123 @@
+use core::fmt;
+
+use pi::interrupt::Interrupt;
+use pi::pl011::{self, Pl011};
+use pi::uart::MiniUart;
+use shim::io;
+
+/// One of the two UARTs the console can be attached to. Both share GPIO pins
+/// 14 and 15: the console UART owns them, except while the GDB stub borrows
+/// them for the other UART (see `Uart::spare()`).
+pub enum Uart {
+    Mini(MiniUart),
+    Pl011(Pl011),
+}
+
+impl Uart {
+    /// Initializes and returns the console UART: the PL011 if the kernel is
+    /// built with the `pl011-console` feature, the mini UART otherwise.
+    pub fn new() -> Uart {
+        if cfg!(feature = "pl011-console") {
+            Uart::Pl011(Pl011::new(pl011::DEFAULT_BAUD))
+        } else {
+            Uart::Mini(MiniUart::new())
+        }
+    }
+
+    /// Initializes and returns the UART the console is not attached to. On
+    /// the board, it takes GPIO pins 14 and 15 over from the console UART
+    /// until `restore_pins()` is called; QEMU connects each UART to its own
+    /// serial port.
+    pub fn spare() -> Uart {
+        if cfg!(feature = "pl011-console") {
+            Uart::Mini(MiniUart::new())
+        } else {
+            Uart::Pl011(Pl011::new(pl011::DEFAULT_BAUD))
+        }
+    }
+
+    /// Hands GPIO pins 14 and 15 back to the console UART after `spare()`
+    /// took them over. The spare UART must no longer be used.
+    pub fn restore_pins() {
+        if cfg!(feature = "pl011-console") {
+            Pl011::claim_pins();
+        } else {
+            MiniUart::claim_pins();
+        }
+    }
+
+    /// Returns the interrupt raised by the console UART.
+    pub fn interrupt() -> Interrupt {
+        if cfg!(feature = "pl011-console") {
+            Interrupt::Uart
+        } else {
+            Interrupt::Aux
+        }
+    }
+
+    /// Enables or disables the receive interrupt of the UART.
+    pub fn set_rx_interrupt(&mut self, enabled: bool) {
+        match self {
+            Uart::Mini(uart) => uart.set_rx_interrupt(enabled),
+            Uart::Pl011(uart) => uart.set_rx_interrupt(enabled),
+        }
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read.
+    pub fn has_byte(&self) -> bool {
+        match self {
+            Uart::Mini(uart) => uart.has_byte(),
+            Uart::Pl011(uart) => uart.has_byte(),
+        }
+    }
+
+    /// Reads a byte, blocking until one is ready.
+    pub fn read_byte(&mut self) -> u8 {
+        match self {
+            Uart::Mini(uart) => uart.read_byte(),
+            Uart::Pl011(uart) => uart.read_byte(),
+        }
+    }
+
+    /// Writes the byte `byte`, blocking until there is room for it.
+    pub fn write_byte(&mut self, byte: u8) {
+        match self {
+            Uart::Mini(uart) => uart.write_byte(byte),
+            Uart::Pl011(uart) => uart.write_byte(byte),
+        }
+    }
+}
+
+impl io::Read for Uart {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Uart::Mini(uart) => io::Read::read(uart, buf),
+            Uart::Pl011(uart) => io::Read::read(uart, buf),
+        }
+    }
+}
+
+impl io::Write for Uart {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Uart::Mini(uart) => io::Write::write(uart, buf),
+            Uart::Pl011(uart) => io::Write::write(uart, buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Uart::Mini(uart) => io::Write::flush(uart),
+            Uart::Pl011(uart) => io::Write::flush(uart),
+        }
+    }
+}
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self {
+            Uart::Mini(uart) => fmt::Write::write_str(uart, s),
+            Uart::Pl011(uart) => fmt::Write::write_str(uart, s),
+        }
+    }
+}
@@ -0,0 +1,511 @@
+mod packet;
+
+#[cfg(test)]
+mod tests;
+
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::mem;
+use core::ptr;
+
+use aarch64::*;
+use shim::io;
+
+use crate::console::{kprintln, Uart};
+use crate::mutex::Mutex;
+use crate::param::{IO_BASE, PAGE_SIZE, USER_IMG_BASE};
+use crate::process::Id;
+use crate::traps::TrapFrame;
+use crate::VMM;
+
+use self::packet::{Command, Event, Reader, MAX_PACKET_SIZE};
+
+/// The number of registers of GDB's AArch64 target description: `x0`-`x30`,
+/// `sp`, `pc`, `cpsr`, `v0`-`v31`, `fpsr` and `fpcr`.
+const NUM_REGS: usize = 68;
+
+/// The instruction written at software breakpoints: `brk #0`.
+const BRK_INSN: u32 = 0xd420_0000;
+
+/// The most software breakpoints set at once.
+const MAX_BREAKPOINTS: usize = 64;
+
+/// The size of a cache line of the Cortex-A53.
+const CACHE_LINE_SIZE: usize = 64;
+
+/// The reply reporting a stop to GDB: on `SIGTRAP`, for every stop.
+const STOP_REPLY: &[u8] = b"S05";
+
+/// The `M` field of the SPSR of a context running at EL1 on `SP_EL1`.
+const SPSR_EL1H: u64 = 0b0101;
+
+/// The SPSR bits GDB may write through `cpsr`: the condition flags.
+const SPSR_FLAGS: u64 = SPSR_EL1::N | SPSR_EL1::Z | SPSR_EL1::C | SPSR_EL1::V;
+
+#[derive(Debug, Copy, Clone)]
+struct Breakpoint {
+    /// The ID of the process whose address space the breakpoint is set in,
+    /// `None` for one in the kernel, which every address space shares.
+    /// Processes are all loaded at the same address.
+    space: Option<Id>,
+    addr: u64,
+    /// The instruction replaced by `BRK_INSN`.
+    insn: u32,
+    /// Whether GDB removed the breakpoint while stopped in another address
+    /// space. `BRK_INSN` is left in place until the process stops, or
+    /// executes it, as it cannot be written from there.
+    removed: bool,
+}
+
+/// A single step in progress.
+#[derive(Debug, Copy, Clone)]
+struct Step {
+    /// The core executing the step. `MDSCR_EL1` is banked per core.
+    core: usize,
+    /// The `D` and `I` bits of the SPSR of the stepped context, overridden
+    /// for the step.
+    spsr: u64,
+}
+
+/// A GDB remote stub, serving the GDB Remote Serial Protocol over the UART
+/// the console is not attached to (see `Uart::spare()`). GDB can read and
+/// write the registers of the stopped context and memory in its address
+/// space, continue, single-step, and set software breakpoints in the kernel's
+/// `.text` as well as in processes.
+///
+/// On the board, both UARTs share the same pins: the console is cut off from
+/// them from the first stop until GDB detaches or kills the target.
+///
+/// Only the core that stopped waits for GDB: the others keep running. A core
+/// stopping while GDB is busy with another one waits for its turn.
+struct Stub {
+    uart: Option<Uart>,
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+    step: Option<Step>,
+    /// Whether GDB is attached, i.e. expects a stop reply when a core stops.
+    attached: bool,
+}
+
+static STUB: Mutex<Stub> = Mutex::new(Stub {
+    uart: None,
+    breakpoints: [None; MAX_BREAKPOINTS],
+    step: None,
+    attached: false,
+});
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// Returns `true` if `[addr, addr + len)` is mapped for `access` from EL1 in
+/// the current translation regime.
+fn is_accessible(addr: u64, len: usize, access: Access) -> bool {
+    if len == 0 {
+        return true;
+    }
+    let last = match addr.checked_add(len as u64 - 1) {
+        Some(last) => last as usize,
+        None => return false,
+    };
+
+    let mut page = addr as usize & !(PAGE_SIZE - 1);
+    loop {
+        let failed = unsafe {
+            match access {
+                Access::Read => at_s1e1r(page),
+                Access::Write => at_s1e1w(page),
+            }
+            PAR_EL1.get_masked(PAR_EL1::F) != 0
+        };
+        if failed {
+            return false;
+        }
+        match page.checked_add(PAGE_SIZE) {
+            Some(next) if next <= last => page = next,
+            _ => return true,
+        }
+    }
+}
+
+/// Reads `buf.len()` bytes at `addr`. Returns `false`, reading nothing, if
+/// any of them is not mapped.
+fn read_memory(addr: u64, buf: &mut [u8]) -> bool {
+    if !is_accessible(addr, buf.len(), Access::Read) {
+        return false;
+    }
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = unsafe { ptr::read_volatile((addr as usize + i) as *const u8) };
+    }
+    true
+}
+
+/// Writes `data` at `addr`, making it visible to instruction fetches.
+/// Returns `false`, writing nothing, if any byte is not mapped writable. The
+/// kernel's `.text` is made writable for the duration of the write.
+fn write_memory(addr: u64, data: &[u8]) -> bool {
+    let kernel = addr < IO_BASE as u64;
+    if kernel {
+        VMM.set_text_writable(true);
+    }
+
+    let written = is_accessible(addr, data.len(), Access::Write);
+    if written {
+        for (i, &byte) in data.iter().enumerate() {
+            unsafe { ptr::write_volatile((addr as usize + i) as *mut u8, byte) };
+        }
+        let start = addr as usize & !(CACHE_LINE_SIZE - 1);
+        for line in (start..addr as usize + data.len()).step_by(CACHE_LINE_SIZE) {
+            unsafe {
+                dc_cvau(line);
+                ic_ivau(line);
+            }
+        }
+    }
+
+    if kernel {
+        VMM.set_text_writable(false);
+    }
+    written
+}
+
+/// Returns the address space `addr` is in, as seen from the stopped context
+/// `tf`: that of its process for a user address (see `Breakpoint::space`).
+fn address_space(addr: u64, tf: &TrapFrame) -> Option<Id> {
+    if addr >= USER_IMG_BASE as u64 {
+        Some(tf.tpidr)
+    } else {
+        None
+    }
+}
+
+/// Returns the reply to a request that succeeds or fails without data.
+fn status(ok: bool) -> &'static [u8] {
+    if ok { b"OK" } else { b"E14" }
+}
+
+/// Returns the size in bytes of register `n`.
+fn register_size(n: usize) -> usize {
+    match n {
+        0..=32 => 8,
+        33 => 4,
+        34..=65 => 16,
+        _ => 4,
+    }
+}
+
+/// Returns the stack pointer of the stopped context. A context running on
+/// `SP_EL1` was interrupted right above its trap frame.
+fn stack_pointer(tf: &TrapFrame) -> u64 {
+    if tf.spsr & SPSR_EL1::M == SPSR_EL1H {
+        tf as *const TrapFrame as u64 + mem::size_of::<TrapFrame>() as u64
+    } else {
+        tf.sp
+    }
+}
+
+/// Appends register `n` of `tf` to `reply`, in hexadecimal.
+fn read_register(tf: &TrapFrame, n: usize, reply: &mut Vec<u8>) {
+    let bytes = match n {
+        0..=30 => tf.x[n] as u128,
+        31 => stack_pointer(tf) as u128,
+        32 => tf.elr as u128,
+        33 => tf.spsr as u128,
+        34..=65 => tf.q[n - 34],
+        66 => tf.fpsr as u128,
+        _ => tf.fpcr as u128,
+    }.to_le_bytes();
+    packet::push_hex(reply, &bytes[..register_size(n)]);
+}
+
+/// Sets register `n` of `tf` to `bytes`, `register_size(n)` bytes in target
+/// byte order. The stack pointer of a context running on `SP_EL1` and the
+/// SPSR bits but the condition flags are left as is.
+fn write_register(tf: &mut TrapFrame, n: usize, bytes: &[u8]) {
+    let value = bytes.iter().rev().fold(0u128, |value, &b| value << 8 | b as u128);
+    match n {
+        0..=30 => tf.x[n] = value as u64,
+        31 if tf.spsr & SPSR_EL1::M != SPSR_EL1H => tf.sp = value as u64,
+        31 => (),
+        32 => tf.elr = value as u64,
+        33 => tf.spsr = tf.spsr & !SPSR_FLAGS | value as u64 & SPSR_FLAGS,
+        34..=65 => tf.q[n - 34] = value,
+        66 => tf.fpsr = value as u64,
+        _ => tf.fpcr = value as u64,
+    }
+}
+
+impl Stub {
+    /// Returns the slot of the breakpoint at `addr` in the address space of
+    /// `tf`, removed or not.
+    fn breakpoint(&self, addr: u64, tf: &TrapFrame) -> Option<usize> {
+        let space = address_space(addr, tf);
+        self.breakpoints.iter()
+            .position(|bp| bp.map_or(false, |bp| bp.addr == addr && bp.space == space))
+    }
+
+    fn insert_breakpoint(&mut self, addr: u64, tf: &TrapFrame) -> bool {
+        if let Some(slot) = self.breakpoint(addr, tf) {
+            // `BRK_INSN` is still in place if the breakpoint was removed.
+            self.breakpoints[slot].as_mut().unwrap().removed = false;
+            return true;
+        }
+        let slot = match self.breakpoints.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => return false,
+        };
+
+        let mut insn = [0; 4];
+        if addr % 4 != 0 || !read_memory(addr, &mut insn)
+            || !write_memory(addr, &BRK_INSN.to_le_bytes()) {
+            return false;
+        }
+        self.breakpoints[slot] = Some(Breakpoint {
+            space: address_space(addr, tf),
+            addr,
+            insn: u32::from_le_bytes(insn),
+            removed: false,
+        });
+        true
+    }
+
+    /// Writes back the instruction replaced by the breakpoint in `slot`,
+    /// which must be in the address space of the stopped context or in the
+    /// kernel, and frees the slot.
+    fn restore(&mut self, slot: usize) -> bool {
+        let bp = self.breakpoints[slot].take().unwrap();
+        write_memory(bp.addr, &bp.insn.to_le_bytes())
+    }
+
+    /// Removes the breakpoint at `addr`. GDB does not tell address spaces
+    /// apart: if there is none at `addr` in that of `tf`, a breakpoint at
+    /// `addr` set in another one is marked as removed instead.
+    fn remove_breakpoint(&mut self, addr: u64, tf: &TrapFrame) -> bool {
+        if let Some(slot) = self.breakpoint(addr, tf) {
+            return self.restore(slot);
+        }
+        let other = self.breakpoints.iter_mut()
+            .filter_map(Option::as_mut)
+            .find(|bp| bp.addr == addr && !bp.removed);
+        match other {
+            Some(bp) => {
+                bp.removed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes every breakpoint, marking those in other address spaces than
+    /// that of `tf` as removed.
+    fn remove_breakpoints(&mut self, tf: &TrapFrame) {
+        for slot in 0..MAX_BREAKPOINTS {
+            match self.breakpoints[slot] {
+                Some(bp) if bp.space.is_none() || bp.space == Some(tf.tpidr) => {
+                    self.restore(slot);
+                }
+                Some(ref mut bp) => bp.removed = true,
+                None => (),
+            }
+        }
+    }
+
+    /// Writes back the instructions of the breakpoints removed from the
+    /// address space of `tf` while it was not stopped.
+    fn restore_removed(&mut self, tf: &TrapFrame) {
+        for slot in 0..MAX_BREAKPOINTS {
+            match self.breakpoints[slot] {
+                Some(bp) if bp.removed && bp.space == Some(tf.tpidr) => {
+                    self.restore(slot);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Arranges for a software step exception to be taken once `tf` has
+    /// executed a single instruction. Debug exceptions are only taken from
+    /// EL1 with `MDSCR_EL1.KDE` set and `D` clear, and IRQs are masked so
+    /// that the step is not spent in the tick handler.
+    fn begin_step(&mut self, tf: &mut TrapFrame) {
+        self.step = Some(Step { core: affinity(), spsr: tf.spsr & (SPSR_EL1::D | SPSR_EL1::I) });
+        tf.spsr = tf.spsr & !SPSR_EL1::D | SPSR_EL1::I | SPSR_EL1::SS;
+        unsafe {
+            // The OS lock, set at reset, disables debug exceptions.
+            OSLAR_EL1.set(0);
+            MDSCR_EL1.set(MDSCR_EL1.get() | MDSCR_EL1::KDE | MDSCR_EL1::SS);
+            isb();
+        }
+    }
+
+    /// Undoes `begin_step()` if a step is in progress on this core.
+    fn end_step(&mut self, tf: &mut TrapFrame) {
+        match self.step {
+            Some(step) if step.core == affinity() => {
+                tf.spsr = tf.spsr & !(SPSR_EL1::D | SPSR_EL1::I | SPSR_EL1::SS) | step.spsr;
+                unsafe { MDSCR_EL1.set(MDSCR_EL1.get() & !(MDSCR_EL1::KDE | MDSCR_EL1::SS)) };
+                self.step = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns the reply to `command`, which must not resume the target.
+    fn reply(&mut self, command: Command, tf: &mut TrapFrame) -> Vec<u8> {
+        let mut reply = Vec::new();
+        match command {
+            Command::Halted => reply.extend_from_slice(STOP_REPLY),
+            Command::ReadRegisters => {
+                for n in 0..NUM_REGS {
+                    read_register(tf, n, &mut reply);
+                }
+            }
+            Command::WriteRegisters(bytes) => {
+                let mut bytes = &bytes[..];
+                for n in 0..NUM_REGS {
+                    let size = register_size(n);
+                    if bytes.len() < size {
+                        break;
+                    }
+                    write_register(tf, n, &bytes[..size]);
+                    bytes = &bytes[size..];
+                }
+                reply.extend_from_slice(b"OK");
+            }
+            Command::ReadRegister(n) if n < NUM_REGS => read_register(tf, n, &mut reply),
+            Command::WriteRegister(n, ref bytes) if n < NUM_REGS && bytes.len() == register_size(n) => {
+                write_register(tf, n, bytes);
+                reply.extend_from_slice(b"OK");
+            }
+            Command::ReadRegister(_) | Command::WriteRegister(..) => reply.extend_from_slice(b"E01"),
+            Command::ReadMemory { addr, len } => {
+                let mut buf = vec![0; len.min(MAX_PACKET_SIZE / 2)];
+                if read_memory(addr, &mut buf) {
+                    packet::push_hex(&mut reply, &buf);
+                } else {
+                    reply.extend_from_slice(b"E14");
+                }
+            }
+            Command::WriteMemory { addr, data } => {
+                reply.extend_from_slice(status(write_memory(addr, &data)));
+            }
+            Command::InsertBreakpoint(addr) => {
+                reply.extend_from_slice(status(self.insert_breakpoint(addr, tf)));
+            }
+            Command::RemoveBreakpoint(addr) => {
+                reply.extend_from_slice(status(self.remove_breakpoint(addr, tf)));
+            }
+            Command::SetThread => reply.extend_from_slice(b"OK"),
+            Command::Supported => {
+                reply.extend_from_slice(format!("PacketSize={:x}", MAX_PACKET_SIZE).as_bytes());
+            }
+            Command::Attached => reply.extend_from_slice(b"1"),
+            Command::Continue(_) | Command::Step(_) | Command::Detach | Command::Kill
+            | Command::Unsupported => (),
+        }
+        reply
+    }
+
+    /// Reports the stop of `tf` to GDB and serves its requests until it
+    /// resumes the target.
+    fn serve(&mut self, tf: &mut TrapFrame) {
+        self.end_step(tf);
+        self.restore_removed(tf);
+        if !self.attached {
+            // Printed before the spare UART takes the pins of the console.
+            kprintln!("gdbstub: core {} stopped at {:#x}, waiting for GDB", affinity(), tf.elr);
+        }
+        let mut uart = self.uart.take().unwrap_or_else(Uart::spare);
+        let send = |uart: &mut Uart, data: &[u8]| {
+            for &byte in packet::frame(data).iter() {
+                uart.write_byte(byte);
+            }
+        };
+
+        let mut last = STOP_REPLY.to_vec();
+        if self.attached {
+            send(&mut uart, &last);
+        }
+
+        let mut reader = Reader::new();
+        loop {
+            let packet = match reader.push(uart.read_byte()) {
+                None => continue,
+                Some(Event::Packet(packet)) => packet,
+                Some(Event::Corrupt) => {
+                    uart.write_byte(b'-');
+                    continue;
+                }
+                Some(Event::Nack) => {
+                    send(&mut uart, &last);
+                    continue;
+                }
+                Some(Event::Interrupt) => b"?".to_vec(),
+            };
+            uart.write_byte(b'+');
+            self.attached = true;
+
+            let command = match Command::parse(&packet) {
+                Some(command) => command,
+                None => {
+                    last = b"E01".to_vec();
+                    send(&mut uart, &last);
+                    continue;
+                }
+            };
+            match command {
+                Command::Continue(addr) => {
+                    tf.elr = addr.unwrap_or(tf.elr);
+                    break;
+                }
+                Command::Step(addr) => {
+                    tf.elr = addr.unwrap_or(tf.elr);
+                    self.begin_step(tf);
+                    break;
+                }
+                Command::Detach | Command::Kill => {
+                    // The target keeps running without GDB.
+                    self.remove_breakpoints(tf);
+                    self.attached = false;
+                    if command == Command::Detach {
+                        send(&mut uart, b"OK");
+                    }
+                    // The reply must be out before the pins are switched.
+                    io::Write::flush(&mut uart).ok();
+                    Uart::restore_pins();
+                    return;
+                }
+                command => {
+                    last = self.reply(command, tf);
+                    send(&mut uart, &last);
+                }
+            }
+        }
+        self.uart = Some(uart);
+    }
+}
+
+/// Stops `tf`, which executed a `brk` instruction, until GDB resumes it. A
+/// `brk` compiled into the code, rather than set by GDB in the address space
+/// of `tf`, is stepped over: resuming at it would stop again right away. A
+/// breakpoint GDB removed from another address space does not stop `tf`: the
+/// instruction it replaced is written back and executed.
+pub fn handle_brk(tf: &mut TrapFrame) {
+    let mut stub = STUB.lock();
+    match stub.breakpoint(tf.elr, tf) {
+        Some(slot) if stub.breakpoints[slot].unwrap().removed => {
+            stub.restore(slot);
+            return;
+        }
+        Some(_) => (),
+        None => tf.elr += 4,
+    }
+    stub.serve(tf);
+}
+
+/// Stops `tf`, which took a software step exception after GDB had it execute
+/// a single instruction, until GDB resumes it.
+pub fn handle_step(tf: &mut TrapFrame) {
+    STUB.lock().serve(tf);
+}
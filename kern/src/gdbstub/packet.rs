@@ -0,0 +1,259 @@
+use alloc::vec::Vec;
+
+/// The largest packet GDB may send, advertised in the reply to
+/// `qSupported`. Memory reads are capped so that their reply fits too.
+pub const MAX_PACKET_SIZE: usize = 0x1000;
+
+/// The byte GDB sends to interrupt a running target.
+const INTERRUPT: u8 = 0x03;
+
+/// Returns the digit of the nibble `n` in lowercase hexadecimal.
+fn hex_digit(n: u8) -> u8 {
+    b"0123456789abcdef"[(n & 0xf) as usize]
+}
+
+/// Returns the value of the hexadecimal digit `c`, if it is one.
+fn from_hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Returns the modulo 256 sum of `data`, the checksum of a packet.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Appends `bytes` to `buf`, two hexadecimal digits per byte.
+pub fn push_hex(buf: &mut Vec<u8>, bytes: &[u8]) {
+    for &b in bytes {
+        buf.push(hex_digit(b >> 4));
+        buf.push(hex_digit(b));
+    }
+}
+
+/// Decodes a string of hexadecimal byte pairs.
+pub fn decode_hex(s: &[u8]) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    s.chunks(2)
+        .map(|pair| Some(from_hex_digit(pair[0])? << 4 | from_hex_digit(pair[1])?))
+        .collect()
+}
+
+/// Parses a hexadecimal number, most significant digit first.
+pub fn parse_hex(s: &[u8]) -> Option<u64> {
+    if s.is_empty() || s.len() > 16 {
+        return None;
+    }
+    s.iter().try_fold(0, |n, &c| Some(n << 4 | from_hex_digit(c)? as u64))
+}
+
+/// Returns `data` framed as a packet: `$data#cs`, `cs` being the checksum in
+/// hexadecimal. `data` must not contain `$`, `#`, `}` or `*`.
+pub fn frame(data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + 4);
+    packet.push(b'$');
+    packet.extend_from_slice(data);
+    packet.push(b'#');
+    push_hex(&mut packet, &[checksum(data)]);
+    packet
+}
+
+/// What the bytes received from GDB amount to.
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    /// A packet with a valid checksum, unescaped. It must be acknowledged
+    /// with `+`.
+    Packet(Vec<u8>),
+    /// A packet with an invalid checksum. It must be rejected with `-`.
+    Corrupt,
+    /// GDB rejected the last packet sent: it must be sent again.
+    Nack,
+    /// GDB asks the target to stop.
+    Interrupt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Data,
+    Escape,
+    Checksum,
+    ChecksumLow(u8),
+}
+
+/// Reassembles the packets GDB sends, one byte at a time.
+pub struct Reader {
+    state: State,
+    data: Vec<u8>,
+    sum: u8,
+}
+
+impl Reader {
+    pub fn new() -> Reader {
+        Reader { state: State::Idle, data: Vec::new(), sum: 0 }
+    }
+
+    /// Feeds the received byte `byte`, returning the event it completes, if
+    /// any. Acknowledgements (`+`) and stray bytes between packets are
+    /// ignored.
+    pub fn push(&mut self, byte: u8) -> Option<Event> {
+        match (self.state, byte) {
+            (_, b'$') => {
+                self.state = State::Data;
+                self.data.clear();
+                self.sum = 0;
+            }
+            (State::Idle, b'-') => return Some(Event::Nack),
+            (State::Idle, INTERRUPT) => return Some(Event::Interrupt),
+            (State::Idle, _) => (),
+            (State::Data, b'#') => self.state = State::Checksum,
+            (State::Data, _) => {
+                self.sum = self.sum.wrapping_add(byte);
+                if byte == b'}' {
+                    self.state = State::Escape;
+                } else {
+                    self.data.push(byte);
+                }
+            }
+            (State::Escape, _) => {
+                self.sum = self.sum.wrapping_add(byte);
+                self.data.push(byte ^ 0x20);
+                self.state = State::Data;
+            }
+            (State::Checksum, _) => match from_hex_digit(byte) {
+                Some(high) => self.state = State::ChecksumLow(high),
+                None => {
+                    self.state = State::Idle;
+                    return Some(Event::Corrupt);
+                }
+            },
+            (State::ChecksumLow(high), _) => {
+                self.state = State::Idle;
+                return match from_hex_digit(byte) {
+                    Some(low) if high << 4 | low == self.sum => {
+                        Some(Event::Packet(core::mem::replace(&mut self.data, Vec::new())))
+                    }
+                    _ => Some(Event::Corrupt),
+                };
+            }
+        }
+        None
+    }
+}
+
+/// The requests the stub serves.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `?`: why the target stopped.
+    Halted,
+    /// `g`
+    ReadRegisters,
+    /// `G data`: the registers in the order of `g`, in target byte order.
+    WriteRegisters(Vec<u8>),
+    /// `p n`
+    ReadRegister(usize),
+    /// `P n=data`
+    WriteRegister(usize, Vec<u8>),
+    /// `m addr,length`
+    ReadMemory { addr: u64, len: usize },
+    /// `M addr,length:data`
+    WriteMemory { addr: u64, data: Vec<u8> },
+    /// `c [addr]`: resume, at `addr` if given.
+    Continue(Option<u64>),
+    /// `s [addr]`: execute a single instruction, at `addr` if given.
+    Step(Option<u64>),
+    /// `Z0,addr,kind`: insert a software breakpoint.
+    InsertBreakpoint(u64),
+    /// `z0,addr,kind`: remove a software breakpoint.
+    RemoveBreakpoint(u64),
+    /// `H op thread`: there is a single thread, the stopped context.
+    SetThread,
+    /// `qSupported`
+    Supported,
+    /// `qAttached`: the target was running before GDB attached.
+    Attached,
+    /// `D`
+    Detach,
+    /// `k`
+    Kill,
+    /// Any other packet: answered with an empty packet, which tells GDB the
+    /// packet is not supported.
+    Unsupported,
+}
+
+/// Splits `s` at the first `sep`.
+fn split(s: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let i = s.iter().position(|&c| c == sep)?;
+    Some((&s[..i], &s[i + 1..]))
+}
+
+/// Parses the optional resume address of `c` and `s`.
+fn resume_addr(s: &[u8]) -> Option<Option<u64>> {
+    if s.is_empty() {
+        Some(None)
+    } else {
+        parse_hex(s).map(Some)
+    }
+}
+
+/// Parses the address of a `Z0` or `z0` packet, `None` if the packet is
+/// malformed. Other breakpoint types are not supported.
+fn breakpoint_addr(s: &[u8]) -> Option<Option<u64>> {
+    let (kind, rest) = split(s, b',')?;
+    if kind != b"0" {
+        return Some(None);
+    }
+    let (addr, _) = split(rest, b',')?;
+    parse_hex(addr).map(Some)
+}
+
+impl Command {
+    /// Parses the data of a packet. Returns `None` if a supported packet is
+    /// malformed.
+    pub fn parse(packet: &[u8]) -> Option<Command> {
+        use self::Command::*;
+        let (&kind, args) = match packet.split_first() {
+            Some(split) => split,
+            None => return Some(Unsupported),
+        };
+        Some(match kind {
+            b'?' => Halted,
+            b'g' => ReadRegisters,
+            b'G' => WriteRegisters(decode_hex(args)?),
+            b'p' => ReadRegister(parse_hex(args)? as usize),
+            b'P' => {
+                let (n, value) = split(args, b'=')?;
+                WriteRegister(parse_hex(n)? as usize, decode_hex(value)?)
+            }
+            b'm' => {
+                let (addr, len) = split(args, b',')?;
+                ReadMemory { addr: parse_hex(addr)?, len: parse_hex(len)? as usize }
+            }
+            b'M' => {
+                let (addr, rest) = split(args, b',')?;
+                let (len, data) = split(rest, b':')?;
+                let data = decode_hex(data)?;
+                if data.len() as u64 != parse_hex(len)? {
+                    return None;
+                }
+                WriteMemory { addr: parse_hex(addr)?, data }
+            }
+            b'c' => Continue(resume_addr(args)?),
+            b's' => Step(resume_addr(args)?),
+            b'Z' => breakpoint_addr(args)?.map_or(Unsupported, InsertBreakpoint),
+            b'z' => breakpoint_addr(args)?.map_or(Unsupported, RemoveBreakpoint),
+            b'H' => SetThread,
+            b'q' if args.starts_with(b"Supported") => Supported,
+            b'q' if args.starts_with(b"Attached") => Attached,
+            b'D' => Detach,
+            b'k' => Kill,
+            _ => Unsupported,
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::vec::Vec;
+
+use super::packet::{decode_hex, frame, parse_hex, push_hex, Command, Event, Reader};
+
+/// Feeds `bytes` to a new `Reader`, returning the events they complete.
+fn read(bytes: &[u8]) -> Vec<Event> {
+    let mut reader = Reader::new();
+    bytes.iter().filter_map(|&b| reader.push(b)).collect()
+}
+
+#[test]
+fn framing() {
+    assert_eq!(frame(b"OK"), b"$OK#9a".to_vec());
+    assert_eq!(frame(b""), b"$#00".to_vec());
+    assert_eq!(frame(b"S05"), b"$S05#b8".to_vec());
+}
+
+#[test]
+fn hex() {
+    let mut buf = Vec::new();
+    push_hex(&mut buf, &[0x00, 0x7f, 0xa5, 0xff]);
+    assert_eq!(buf, b"007fa5ff".to_vec());
+    assert_eq!(decode_hex(b"007fA5ff"), Some(vec![0x00, 0x7f, 0xa5, 0xff]));
+    assert_eq!(decode_hex(b"007"), None);
+    assert_eq!(decode_hex(b"0g"), None);
+
+    assert_eq!(parse_hex(b"ffffffffc0000000"), Some(0xffff_ffff_c000_0000));
+    assert_eq!(parse_hex(b"1f"), Some(0x1f));
+    assert_eq!(parse_hex(b""), None);
+    assert_eq!(parse_hex(b"10000000000000000"), None);
+}
+
+#[test]
+fn reader() {
+    assert_eq!(read(b"+$g#67"), vec![Event::Packet(b"g".to_vec())]);
+    assert_eq!(read(b"$g#68"), vec![Event::Corrupt]);
+    assert_eq!(read(b"$g#6x"), vec![Event::Corrupt]);
+    assert_eq!(read(b"-\x03"), vec![Event::Nack, Event::Interrupt]);
+    // A new packet starts over an incomplete one.
+    assert_eq!(read(b"$m0,4$?#3f"), vec![Event::Packet(b"?".to_vec())]);
+    // Escaped bytes count in the checksum as sent.
+    assert_eq!(read(b"$}]#da"), vec![Event::Packet(b"}".to_vec())]);
+    assert_eq!(read(b"$c#63$s#73"),
+        vec![Event::Packet(b"c".to_vec()), Event::Packet(b"s".to_vec())]);
+}
+
+#[test]
+fn commands() {
+    let parse = |s: &[u8]| Command::parse(s);
+    assert_eq!(parse(b"?"), Some(Command::Halted));
+    assert_eq!(parse(b"g"), Some(Command::ReadRegisters));
+    assert_eq!(parse(b"G0102"), Some(Command::WriteRegisters(vec![1, 2])));
+    assert_eq!(parse(b"p20"), Some(Command::ReadRegister(32)));
+    assert_eq!(parse(b"P1f=0000080000000000"),
+        Some(Command::WriteRegister(31, vec![0, 0, 8, 0, 0, 0, 0, 0])));
+    assert_eq!(parse(b"m80000,40"), Some(Command::ReadMemory { addr: 0x80000, len: 0x40 }));
+    assert_eq!(parse(b"M80000,2:1f20"),
+        Some(Command::WriteMemory { addr: 0x80000, data: vec![0x1f, 0x20] }));
+    assert_eq!(parse(b"c"), Some(Command::Continue(None)));
+    assert_eq!(parse(b"s80004"), Some(Command::Step(Some(0x80004))));
+    assert_eq!(parse(b"Z0,80100,4"), Some(Command::InsertBreakpoint(0x80100)));
+    assert_eq!(parse(b"z0,80100,4"), Some(Command::RemoveBreakpoint(0x80100)));
+    assert_eq!(parse(b"Z1,80100,4"), Some(Command::Unsupported));
+    assert_eq!(parse(b"Hg0"), Some(Command::SetThread));
+    assert_eq!(parse(b"qSupported:multiprocess+;swbreak+"), Some(Command::Supported));
+    assert_eq!(parse(b"qAttached"), Some(Command::Attached));
+    assert_eq!(parse(b"D"), Some(Command::Detach));
+    assert_eq!(parse(b"k"), Some(Command::Kill));
+    assert_eq!(parse(b"vMustReplyEmpty"), Some(Command::Unsupported));
+    assert_eq!(parse(b""), Some(Command::Unsupported));
+
+    assert_eq!(parse(b"m80000"), None);
+    assert_eq!(parse(b"M80000,3:1f20"), None);
+    assert_eq!(parse(b"P1f"), None);
+    assert_eq!(parse(b"Z0,80100"), None);
+}
@@ -6,8 +6,8 @@ use core::ptr::write_volatile;
 mod oom;
 mod panic;
 
-use crate::kmain;
 use crate::param::*;
+use crate::{kmain, kmain2, VMM};
 
 global_asm!(include_str!("init/vectors.s"));
 
@@ -19,15 +19,35 @@ global_asm!(include_str!("init/vectors.s"));
 // so, no debug build support!
 //
 
+/// Kernel entry point. When the firmware passes a device tree instead of
+/// ATAGs, `fdt` holds its address (`x0`); otherwise it is zero.
 #[no_mangle]
-pub unsafe extern "C" fn _start() -> ! {
+pub unsafe extern "C" fn _start(fdt: usize) -> ! {
     if MPIDR_EL1.get_value(MPIDR_EL1::Aff0) == 0 {
         SP.set(KERN_STACK_BASE);
-        kinit()
+        kinit(fdt)
     }
     unreachable!()
 }
 
+extern "C" {
+    static __stacks_beg: u8;
+}
+
+/// Returns the top of the EL1 stack of core `core` (see `KERN_STACK_SIZE`).
+fn kern_stack_top(core: usize) -> usize {
+    let base = unsafe { &__stacks_beg as *const u8 as usize };
+    base + (core + 1) * KERN_STACK_SLOT
+}
+
+/// Entry point of the secondary cores, which the firmware releases from its
+/// spin loop when their spin-table slot is written.
+#[no_mangle]
+pub unsafe extern "C" fn start2() -> ! {
+    SP.set(kern_stack_top(affinity()));
+    kinit2()
+}
+
 unsafe fn zeros_bss() {
     extern "C" {
         static mut __bss_beg: u64;
@@ -104,9 +124,43 @@ unsafe fn switch_to_el1() {
 }
 
 #[no_mangle]
-unsafe fn kinit() -> ! {
+unsafe fn kinit(fdt: usize) -> ! {
     zeros_bss();
+    pi::fdt::set_base(fdt);
     switch_to_el2();
     switch_to_el1();
     kmain();
 }
+
+#[no_mangle]
+unsafe fn kinit2() -> ! {
+    switch_to_el2();
+    switch_to_el1();
+    VMM.setup();
+
+    // Tell core 0 that this core is up, now that the MMU is on: the write
+    // goes through the coherent caches.
+    SPINNING_BASE.add(affinity()).write_volatile(0);
+    kmain2();
+}
+
+/// Wakes the secondary cores up through their spin-table slots, and waits
+/// until each of them has its MMU on.
+///
+/// The caller should assure that `VMM.initialize()` has been called before
+/// calling this function.
+pub unsafe fn initialize_app_cores() {
+    for core in 1..NCORES {
+        let slot = SPINNING_BASE.add(core);
+        slot.write_volatile(start2 as usize);
+        // The firmware polls the slot with the core's caches off.
+        dc_civac(slot as usize);
+    }
+    sev();
+
+    for core in 1..NCORES {
+        while SPINNING_BASE.add(core).read_volatile() != 0 {
+            // Do nothing
+        }
+    }
+}
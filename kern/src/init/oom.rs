@@ -1,6 +1,11 @@
 use core::alloc::Layout;
 
+use crate::console::kprintln;
+use crate::ALLOCATOR;
+
 #[alloc_error_handler]
-pub fn oom(_layout: Layout) -> ! {
+pub fn oom(layout: Layout) -> ! {
+    kprintln!("failed to allocate {} bytes (align {})", layout.size(), layout.align());
+    kprintln!("{}", ALLOCATOR.stats());
     panic!("OOM");
 }
@@ -1,7 +1,64 @@
 use core::panic::PanicInfo;
-use crate::console::{kprint, kprintln, CONSOLE};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aarch64::{ELR_EL1, ESR_EL1, FAR_EL1};
+use pi::common::IO_BASE;
+
+use crate::console::{self, kprint, kprintln, CONSOLE};
+use crate::ksyms::{self, Demangle};
+use crate::traps::{self, TrapFrame};
+
+/// The maximum number of frames printed by `backtrace()`.
+const MAX_FRAMES: usize = 32;
+
+/// Whether a panic is being reported. Only the first one is: a panic in the
+/// panic handler, or on another core meanwhile, stops its core silently.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Prints `pc` and the function it belongs to, if known.
+fn print_pc(pc: usize) {
+    match ksyms::lookup(pc) {
+        Some((name, offset)) => kprintln!("  {:#010x}  {}+{:#x}", pc, Demangle(name), offset),
+        None => kprintln!("  {:#010x}  ??", pc),
+    }
+}
+
+/// Prints the call sites found by walking the frame-pointer chain from `fp`.
+/// Each frame record holds the caller's frame pointer, then the return
+/// address. The walk stops at a frame pointer that cannot be a kernel one.
+fn backtrace(mut fp: usize) {
+    for _ in 0..MAX_FRAMES {
+        if fp == 0 || fp % 8 != 0 || fp >= IO_BASE {
+            return;
+        }
+        let (next, lr) = unsafe { (*(fp as *const usize), *((fp + 8) as *const usize)) };
+        if lr < 4 {
+            return;
+        }
+        print_pc(lr - 4);
+        fp = next;
+    }
+}
+
+/// Prints the registers saved in `tf`.
+fn dump_frame(tf: &TrapFrame) {
+    kprintln!("  elr   {:#018x}  spsr  {:#010x}  sp    {:#018x}", tf.elr, tf.spsr, tf.sp);
+    kprintln!("  ttbr0 {:#018x}  ttbr1 {:#018x}  tpidr {}", tf.ttbr0, tf.ttbr1, tf.tpidr);
+    for (i, pair) in tf.x[..30].chunks(2).enumerate() {
+        kprintln!("  x{:02}   {:#018x}  x{:02}   {:#018x}", 2 * i, pair[0], 2 * i + 1, pair[1]);
+    }
+    kprintln!("  lr    {:#018x}", tf.x[30]);
+}
+
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
+    if PANICKING.swap(true, Ordering::SeqCst) {
+        loop {}
+    }
+    // The console may be locked by this very core, which would panic again
+    // on `lock()`, or by a core that will never release it.
+    unsafe { console::force_unlock() };
+
     if let Some(location) = _info.location() {
         kprintln!(r#"
                 (
@@ -26,5 +83,30 @@ fn panic(_info: &PanicInfo) -> ! {
     } else {
       kprintln!("Pi panic in unknown location");
     }
+
+    unsafe {
+        kprintln!("ESR_EL1 {:#010x}  FAR_EL1 {:#x}  ELR_EL1 {:#x}",
+            ESR_EL1.get(), FAR_EL1.get(), ELR_EL1.get());
+    }
+    let fp: usize;
+    unsafe { asm!("mov $0, x29" : "=r"(fp) ::: "volatile") };
+    kprintln!("backtrace:");
+    backtrace(fp);
+
+    if let Some(tf) = traps::active_frame() {
+        kprintln!("while handling an exception, with the trap frame:");
+        dump_frame(tf);
+        // The interrupted context is the kernel's: its backtrace is ours too.
+        if tf.spsr & 0b1100 != 0 {
+            kprintln!("interrupted at:");
+            print_pc(tf.elr as usize);
+            backtrace(tf.x[29] as usize);
+        }
+    }
+    if cfg!(feature = "reboot-on-panic") {
+        kprintln!("Rebooting in 5 seconds...");
+        pi::timer::spin_sleep(core::time::Duration::from_secs(5));
+        pi::pm::reboot();
+    }
     loop {}
 }
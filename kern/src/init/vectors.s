@@ -15,7 +15,16 @@ context_save:
     stp x4, x5,[SP, #-16]!
     stp x2, x3,[SP, #-16]!
     stp x0, x1,[SP, #-16]!
-   
+
+    // The FP/SIMD registers are saved if the interrupted context may use
+    // them: it runs at EL1, or it is a process granted EL0 access on its
+    // first use (CPACR_EL1.FPEN == 0b11). Otherwise their slots are skipped.
+    mrs x0, SPSR_EL1
+    tst x0, #0b1100
+    b.ne 1f
+    mrs x0, CPACR_EL1
+    tbz x0, #21, 2f
+1:
     stp q30, q31, [SP, #-32]!
     stp q28, q29, [SP, #-32]!
     stp q26, q27, [SP, #-32]!
@@ -32,6 +41,15 @@ context_save:
     stp q4, q5, [SP, #-32]!
     stp q2, q3, [SP, #-32]!
     stp q0, q1, [SP, #-32]!
+    mrs x0, FPCR
+    mrs x1, FPSR
+    b 3f
+2:
+    sub SP, SP, #512
+    mov x0, xzr
+    mov x1, xzr
+3:
+    stp x0, x1, [SP, #-16]!
  
     //mrs x4, TPIDR_EL0
     //mrs x3, SP_EL0
@@ -74,12 +92,27 @@ context_restore:
     msr SP_EL0, x2
     msr SPSR_EL1, x1
     msr ELR_EL1, x0
- 
+
+    // Untagged (ASID 0) user translations may belong to another process:
+    // flush them. Tagged ones are kept across context switches.
+    tst     x5, #0xffff000000000000
+    b.ne    1f
     dsb     ishst
     tlbi    vmalle1
     dsb     ish
+1:
     isb
 
+    // Mirrors `context_save`, for the context being returned to.
+    ldp     x0, x1, [SP], #16
+    mrs     x2, SPSR_EL1
+    tst     x2, #0b1100
+    b.ne    2f
+    mrs     x2, CPACR_EL1
+    tbz     x2, #21, 3f
+2:
+    msr     FPCR, x0
+    msr     FPSR, x1
     ldp q0, q1,  [SP], #32
     ldp q2, q3,  [SP], #32
     ldp q4, q5,  [SP], #32
@@ -96,6 +129,10 @@ context_restore:
     ldp q26, q27, [SP], #32
     ldp q28, q29, [SP], #32
     ldp q30, q31, [SP], #32
+    b       4f
+3:
+    add     SP, SP, #512
+4:
 
     ldp x0, x1, [SP], #16
     ldp x2, x3, [SP], #16
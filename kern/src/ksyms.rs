@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests;
+
+use core::fmt;
+
+// The table of code symbols, generated at build time by `build.rs`.
+include!(concat!(env!("OUT_DIR"), "/ksyms.rs"));
+
+extern "C" {
+    static __ksyms_beg: u8;
+    static __ksyms_end: u8;
+}
+
+/// Returns the symbol table embedded in the kernel image. It is empty if the
+/// kernel was built without the symbols of a previous build.
+fn table() -> &'static [u8] {
+    unsafe {
+        let beg = &__ksyms_beg as *const u8;
+        let end = &__ksyms_end as *const u8;
+        core::slice::from_raw_parts(beg, end as usize - beg as usize)
+    }
+}
+
+/// An iterator over the symbols of an encoded table, in increasing address
+/// order. See `build.rs` for the encoding.
+struct Symbols<'a> {
+    table: &'a [u8],
+}
+
+impl<'a> Iterator for Symbols<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        if self.table.len() < 10 {
+            return None;
+        }
+        let mut addr = [0; 8];
+        addr.copy_from_slice(&self.table[..8]);
+        let len = u16::from_le_bytes([self.table[8], self.table[9]]) as usize;
+        let end = (10 + len).min(self.table.len());
+        let name = core::str::from_utf8(&self.table[10..end]).unwrap_or("?");
+        self.table = &self.table[end..];
+        Some((u64::from_le_bytes(addr) as usize, name))
+    }
+}
+
+/// Returns the name of the symbol of `table` that contains `addr` and the
+/// offset of `addr` in it, if any: the last symbol at or before `addr`.
+fn lookup_in(table: &[u8], addr: usize) -> Option<(&str, usize)> {
+    Symbols { table }
+        .take_while(|&(start, _)| start <= addr)
+        .last()
+        .map(|(start, name)| (name, addr - start))
+}
+
+/// Returns the (mangled) name of the kernel function containing `addr` and
+/// the offset of `addr` in it, if it is known.
+pub fn lookup(addr: usize) -> Option<(&'static str, usize)> {
+    lookup_in(table(), addr)
+}
+
+/// Displays a symbol name mangled with the legacy Rust scheme as a path, e.g.
+/// `kernel::shell::shell` for `_ZN6kernel5shell5shell17h0123456789abcdefE`.
+/// Other names are displayed as is.
+pub struct Demangle<'a>(pub &'a str);
+
+/// The escapes of the legacy mangling scheme, and what they stand for.
+const ESCAPES: [(&str, &str); 15] = [
+    ("$SP$", "@"), ("$BP$", "*"), ("$RF$", "&"), ("$LT$", "<"), ("$GT$", ">"),
+    ("$LP$", "("), ("$RP$", ")"), ("$C$", ","), ("$u7e$", "~"), ("$u20$", " "),
+    ("$u27$", "'"), ("$u5b$", "["), ("$u5d$", "]"), ("$u7b$", "{"), ("$u7d$", "}"),
+];
+
+/// Returns `true` if `ident` is the hash ending legacy mangled names.
+fn is_hash(ident: &str) -> bool {
+    ident.len() == 17 && ident.starts_with('h')
+        && ident[1..].bytes().all(|b| (b as char).is_ascii_hexdigit())
+}
+
+/// Writes the identifier `ident` of a legacy mangled name, unescaped.
+fn write_ident(f: &mut fmt::Formatter, mut ident: &str) -> fmt::Result {
+    if ident.starts_with("_$") {
+        ident = &ident[1..];
+    }
+    while !ident.is_empty() {
+        if ident.starts_with("..") {
+            f.write_str("::")?;
+            ident = &ident[2..];
+        } else if let Some(&(escape, c)) = ESCAPES.iter().find(|(e, _)| ident.starts_with(e)) {
+            f.write_str(c)?;
+            ident = &ident[escape.len()..];
+        } else {
+            let end = ident.bytes().skip(1).position(|b| b == b'$' || b == b'.')
+                .map_or(ident.len(), |i| i + 1);
+            f.write_str(&ident[..end])?;
+            ident = &ident[end..];
+        }
+    }
+    Ok(())
+}
+
+impl<'a> fmt::Display for Demangle<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.0.starts_with("_ZN") || !self.0.ends_with('E') {
+            return f.write_str(self.0);
+        }
+        let mut rest = &self.0[3..self.0.len() - 1];
+
+        // Parse the `<length><identifier>` components first: the name is
+        // displayed as is if it turns out to be malformed.
+        let mut idents = [""; 32];
+        let mut count = 0;
+        while !rest.is_empty() {
+            let digits = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+            let len = rest[..digits].parse::<usize>().ok();
+            match len {
+                Some(len) if digits + len <= rest.len() && rest.is_char_boundary(digits + len)
+                    && count < idents.len() => {
+                    idents[count] = &rest[digits..digits + len];
+                    count += 1;
+                    rest = &rest[digits + len..];
+                }
+                _ => return f.write_str(self.0),
+            }
+        }
+
+        if count > 1 && is_hash(idents[count - 1]) {
+            count -= 1;
+        }
+        for (i, ident) in idents[..count].iter().enumerate() {
+            if i > 0 {
+                f.write_str("::")?;
+            }
+            write_ident(f, ident)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use super::{lookup_in, Demangle};
+
+/// Encodes `symbols` the way `build.rs` does.
+fn table(symbols: &[(u64, &str)]) -> Vec<u8> {
+    let mut table = Vec::new();
+    for &(addr, name) in symbols {
+        table.extend_from_slice(&addr.to_le_bytes());
+        table.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        table.extend_from_slice(name.as_bytes());
+    }
+    table
+}
+
+#[test]
+fn lookup() {
+    let table = table(&[(0x80000, "_start"), (0x80100, "kinit"), (0x80400, "kmain")]);
+    assert_eq!(lookup_in(&table, 0x7fffc), None);
+    assert_eq!(lookup_in(&table, 0x80000), Some(("_start", 0)));
+    assert_eq!(lookup_in(&table, 0x80104), Some(("kinit", 4)));
+    assert_eq!(lookup_in(&table, 0x90000), Some(("kmain", 0xfc00)));
+    assert_eq!(lookup_in(&[], 0x80000), None);
+}
+
+#[test]
+fn lookup_truncated() {
+    let mut table = table(&[(0x80000, "_start"), (0x80100, "kinit")]);
+    table.truncate(table.len() - 2);
+    assert_eq!(lookup_in(&table, 0x80104), Some(("kin", 4)));
+}
+
+#[test]
+fn demangle() {
+    let name = |s| Demangle(s).to_string();
+    assert_eq!(name("_ZN6kernel5shell5shell17h0123456789abcdefE"), "kernel::shell::shell");
+    assert_eq!(name("_ZN4core3ptr18real_drop_in_place17h5c5fd8e1d9ad4ab0E"),
+        "core::ptr::real_drop_in_place");
+    assert_eq!(name("_ZN66_$LT$kernel..mutex..Mutex$LT$T$GT$$u20$as$u20$core..fmt..Debug$GT$3fmt17h96a35b3c2b5e2f8aE"),
+        "<kernel::mutex::Mutex<T> as core::fmt::Debug>::fmt");
+    assert_eq!(name("_ZN6kernel5traps16handle_exception28_$u7b$$u7b$closure$u7d$$u7d$17h0000000000000000E"),
+        "kernel::traps::handle_exception::{{closure}}");
+}
+
+#[test]
+fn demangle_passthrough() {
+    let name = |s| Demangle(s).to_string();
+    assert_eq!(name("context_save"), "context_save");
+    assert_eq!(name("_ZN6kernel"), "_ZN6kernel");
+    assert_eq!(name("_ZN99kernelE"), "_ZN99kernelE");
+    assert_eq!(name("_ZN6kernel4mainE"), "kernel::main");
+}
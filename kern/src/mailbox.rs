@@ -0,0 +1,16 @@
+use crate::mutex::Mutex;
+
+/// Held while talking to the firmware through `pi::mailbox`, which does not
+/// synchronize its callers.
+static MAILBOX: Mutex<()> = Mutex::new(());
+
+/// Calls `f`, which talks to the firmware through `pi::mailbox`, with the
+/// mailbox to itself, and returns its result.
+///
+/// The firmware answers the requests of a channel in order, on a single read
+/// register: a core waiting for its reply could otherwise take that of
+/// another, and a request could be sent while the write register is full.
+pub fn with<F: FnOnce() -> R, R>(f: F) -> R {
+    let _guard = MAILBOX.lock();
+    f()
+}
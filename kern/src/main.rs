@@ -17,14 +17,21 @@ mod init;
 extern crate alloc;
 
 pub mod allocator;
+pub mod bench;
 pub mod console;
 pub mod fs;
+pub mod gdbstub;
+pub mod ksyms;
+pub mod mailbox;
 pub mod mutex;
 pub mod shell;
+pub mod spi;
 pub mod param;
 pub mod process;
+pub mod rng;
 pub mod traps;
 pub mod vm;
+pub mod watchdog;
 
 use console::kprintln;
 use allocator::Allocator;
@@ -52,11 +59,19 @@ pub static IRQ: Irq = Irq::uninitialized();
 fn kmain() -> ! {
     // Start the shell.
     unsafe {
+        kprintln!("last reset: {:?}", pi::pm::reset_reason());
         ALLOCATOR.initialize();
         FILESYSTEM.initialize();
         IRQ.initialize();
+        console::rx::initialize();
+        rng::initialize();
         VMM.initialize();
+        if let Err(e) = console::fb::initialize() {
+            kprintln!("framebuffer console unavailable: {:?}", e);
+        }
         SCHEDULER.initialize();
+        #[cfg(not(test))]
+        init::initialize_app_cores();
         SCHEDULER.start();
     }
     
@@ -74,6 +89,12 @@ fn kmain() -> ! {
     // shell::shell("$");
 }
 
+/// Entry point of the secondary cores, once their MMU is on: runs the
+/// scheduler loop of the core.
+fn kmain2() -> ! {
+    SCHEDULER.start();
+}
+
 fn write() -> ! {
     // kprintln!("Console is working!");
     let mut uart = MiniUart::new();
@@ -1,8 +1,13 @@
 use core::fmt;
-use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::sync::atomic::{spin_loop_hint, AtomicBool, AtomicUsize, Ordering};
 use core::cell::UnsafeCell;
 use core::ops::{DerefMut, Deref, Drop};
 
+use aarch64::*;
+
+/// The owner of a mutex nobody holds.
+const NO_OWNER: usize = usize::max_value();
+
 #[repr(align(32))]
 pub struct Mutex<T> {
     data: UnsafeCell<T>,
@@ -14,51 +19,124 @@ unsafe impl<T: Send> Send for Mutex<T> { }
 unsafe impl<T: Send> Sync for Mutex<T> { }
 
 pub struct MutexGuard<'a, T: 'a> {
-    lock: &'a Mutex<T>
+    lock: &'a Mutex<T>,
+    /// The `DAIF` value to restore on release, for guards that masked IRQs.
+    daif: Option<u64>
 }
 
 impl<'a, T> !Send for MutexGuard<'a, T> { }
 unsafe impl<'a, T: Sync> Sync for MutexGuard<'a, T> { }
 
+/// Returns `true` if exclusive accesses work: they need the MMU and the data
+/// cache to be on, which is not yet the case early during boot.
+fn atomics_ready() -> bool {
+    unsafe { SCTLR_EL1.get() & SCTLR_EL1::M != 0 }
+}
+
 impl<T> Mutex<T> {
     pub const fn new(val: T) -> Mutex<T> {
         Mutex {
             lock: AtomicBool::new(false),
-            owner: AtomicUsize::new(usize::max_value()),
+            owner: AtomicUsize::new(NO_OWNER),
             data: UnsafeCell::new(val)
         }
     }
 }
 
 impl<T> Mutex<T> {
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
+    /// Acquires the lock if it is free. Returns `None` otherwise, including
+    /// when the current core holds it.
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        let this = 0;
-        if !self.lock.load(Ordering::Relaxed) || self.owner.load(Ordering::Relaxed) == this {
-            self.lock.store(true, Ordering::Relaxed);
+        let this = affinity();
+        let acquired = if atomics_ready() {
+            self.lock.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+        } else {
+            // Only core 0 runs before the MMU is on: a plain load and store
+            // are enough.
+            !self.lock.load(Ordering::Relaxed) && {
+                self.lock.store(true, Ordering::Relaxed);
+                true
+            }
+        };
+
+        if acquired {
             self.owner.store(this, Ordering::Relaxed);
-            Some(MutexGuard { lock: &self })
+            Some(MutexGuard { lock: &self, daif: None })
         } else {
             None
         }
     }
 
-    // Once MMU/cache is enabled, do the right thing here. For now, we don't
-    // need any real synchronization.
+    /// Acquires the lock, spinning until it is free.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current core already holds the lock: it would spin
+    /// forever.
     #[inline(never)]
     pub fn lock(&self) -> MutexGuard<T> {
-        // Wait until we can "aquire" the lock, then "acquire" it.
         loop {
             match self.try_lock() {
                 Some(guard) => return guard,
-                None => continue
+                None => {
+                    if self.owner.load(Ordering::Relaxed) == affinity() {
+                        panic!("deadlock: core {} acquired a lock it already holds", affinity());
+                    }
+                    spin_loop_hint();
+                }
+            }
+        }
+    }
+
+    /// Like `try_lock()`, but also masks IRQs on the current core until the
+    /// guard is dropped, so that an IRQ handler taking the same lock cannot
+    /// deadlock with the holder.
+    pub fn try_lock_irq(&self) -> Option<MutexGuard<T>> {
+        let daif = unsafe {
+            let daif = DAIF.get();
+            cli();
+            daif
+        };
+        match self.try_lock() {
+            Some(mut guard) => {
+                guard.daif = Some(daif);
+                Some(guard)
+            }
+            None => {
+                unsafe { DAIF.set(daif) };
+                None
             }
         }
     }
 
+    /// Like `lock()`, but also masks IRQs on the current core until the
+    /// guard is dropped, so that an IRQ handler taking the same lock cannot
+    /// deadlock with the holder.
+    pub fn lock_irq(&self) -> MutexGuard<T> {
+        let daif = unsafe {
+            let daif = DAIF.get();
+            cli();
+            daif
+        };
+        let mut guard = self.lock();
+        guard.daif = Some(daif);
+        guard
+    }
+
+    /// Releases the lock, whoever holds it.
+    ///
+    /// # Safety
+    ///
+    /// The holder, if any, may still be using the data: this is only meant
+    /// for the panic handler, which must print whatever state the console is
+    /// left in.
+    pub unsafe fn force_unlock(&self) {
+        self.unlock();
+    }
+
     fn unlock(&self) {
-        self.lock.store(false, Ordering::Relaxed);
+        self.owner.store(NO_OWNER, Ordering::Relaxed);
+        self.lock.store(false, Ordering::Release);
     }
 }
 
@@ -78,7 +156,10 @@ impl<'a, T: 'a> DerefMut for MutexGuard<'a, T> {
 
 impl<'a, T: 'a> Drop for MutexGuard<'a, T> {
     fn drop(&mut self) {
-        self.lock.unlock()
+        self.lock.unlock();
+        if let Some(daif) = self.daif {
+            unsafe { DAIF.set(daif) };
+        }
     }
 }
 
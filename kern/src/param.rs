@@ -21,7 +21,16 @@ const_assert_eq!(
 pub const USER_STACK_BASE: usize = core::usize::MAX & PAGE_MASK; 
 pub const USER_MAX_VM_SIZE: usize = 0x4000_0000;
 const_assert_eq!(USER_IMG_BASE.wrapping_add(USER_MAX_VM_SIZE), 0);
+/// The boot stack of core 0, which grows down from the kernel image's load
+/// address until the scheduler starts.
 pub const KERN_STACK_BASE: usize = 0x80_000;
+/// The size of each core's EL1 stack. The stacks are laid out in the
+/// `.stacks` section of the linker script, each above an unmapped guard page
+/// of its own: core `n`'s stack grows down from
+/// `__stacks_beg + (n + 1) * KERN_STACK_SLOT`.
+pub const KERN_STACK_SIZE: usize = 4 * PAGE_SIZE;
+/// The space taken by a core's EL1 stack and its guard page.
+pub const KERN_STACK_SLOT: usize = PAGE_SIZE + KERN_STACK_SIZE;
 
 /// The `tick` time.
 // When you're ready, change this to something more reasonable.
@@ -2,8 +2,9 @@ mod process;
 mod scheduler;
 mod stack;
 mod state;
+pub mod thread;
 
-pub use self::process::{Id, Process};
+pub use self::process::{Id, Process, ALL_CORES};
 pub use self::scheduler::GlobalScheduler;
 pub use self::stack::Stack;
 pub use self::state::State;
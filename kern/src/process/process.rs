@@ -3,12 +3,15 @@ use shim::io;
 use shim::path::Path;
 
 use aarch64;
+use aarch64::CPACR_EL1;
 use core::cmp::min;
 
 use crate::param::*;
 use crate::process::{Stack, State};
+use crate::process::thread::{self, ThreadFn};
 use crate::traps::TrapFrame;
 use crate::vm::*;
+use crate::vm::asid::Asid;
 use crate::FILESYSTEM;
 use crate::allocator::util::align_down;
 use kernel_api::{OsError, OsResult};
@@ -19,6 +22,9 @@ use io::Read;
 /// Type alias for the type of a process ID.
 pub type Id = u64;
 
+/// The affinity mask of a process allowed to run on every core.
+pub const ALL_CORES: u64 = (1 << NCORES) - 1;
+
 /// A structure that represents the complete state of a process.
 #[derive(Debug)]
 pub struct Process {
@@ -30,6 +36,13 @@ pub struct Process {
     pub vmap: Box<UserPageTable>,
     /// The scheduling state of the process.
     pub state: State,
+    /// The address space identifier tagging the process's TLB entries.
+    pub asid: Asid,
+    /// The cores the process may run on: bit `n` is set for core `n`.
+    pub affinity: u64,
+    /// Whether the process has used the FP/SIMD registers. Until it does,
+    /// they are not part of its context: they are neither saved nor restored.
+    pub uses_fp: bool,
 }
 
 impl Process {
@@ -46,6 +59,9 @@ impl Process {
                     stack: stack,
                     vmap: Box::new(UserPageTable::new()),
                     state: State::Ready,
+                    asid: Asid::default(),
+                    affinity: ALL_CORES,
+                    uses_fp: false,
                 })
             },
             None => Err(OsError::NoMemory)
@@ -58,7 +74,7 @@ impl Process {
     /// `elr` - the address of image base.
     /// `ttbr0` - the base address of kernel page table
     /// `ttbr1` - the base address of user page table
-    /// `spsr` - `F`, `A`, `D` bit should be set.
+    /// `spsr` - `D` bit should be set. FIQs and SErrors are taken at EL0.
     ///
     /// Returns Os Error if do_load fails.
     pub fn load<P: AsRef<Path>>(pn: P) -> OsResult<Process> {
@@ -70,11 +86,33 @@ impl Process {
         p.context.ttbr0 = VMM.get_baddr().as_u64();
         p.context.ttbr1 = p.vmap.get_baddr().as_u64();
         p.context.elr = Self::get_image_base().as_u64();
-        p.context.spsr |= aarch64::SPSR_EL1::F | aarch64::SPSR_EL1::A | aarch64::SPSR_EL1::D;
+        p.context.spsr |= aarch64::SPSR_EL1::D;
         p.context.sp = Self::get_stack_top().as_u64();
         Ok(p)
     }
 
+    /// Creates a kernel thread running `f` on its own stack, with an empty
+    /// user address space.
+    ///
+    /// The thread runs at EL1 with `SP_EL0` pointing to its stack, so that the
+    /// exceptions it takes land on the core's `SP_EL1` stack as those of user
+    /// processes do. IRQs are masked while it runs: kernel threads are
+    /// scheduled cooperatively (see `thread::spawn()`).
+    pub fn kernel_thread(f: ThreadFn) -> OsResult<Process> {
+        use crate::VMM;
+
+        let mut p = Process::new()?;
+        p.context.ttbr0 = VMM.get_baddr().as_u64();
+        p.context.ttbr1 = p.vmap.get_baddr().as_u64();
+        p.context.elr = thread::start as u64;
+        p.context.x[0] = Box::into_raw(Box::new(f)) as u64;
+        p.context.sp = p.stack.top().as_u64();
+        // EL1t: EL1 on `SP_EL0`.
+        p.context.spsr = 0b0100 | aarch64::SPSR_EL1::F | aarch64::SPSR_EL1::A
+            | aarch64::SPSR_EL1::D | aarch64::SPSR_EL1::I;
+        Ok(p)
+    }
+
     /// Creates a process and open a file with given path.
     /// Allocates one page for stack with read/write permission, and N pages with read/write/execute
     /// permission to load file's contents.
@@ -127,6 +165,23 @@ impl Process {
         align_down(usize::max_value(), 16).into()
     }
 
+    /// Grants the process access to the FP/SIMD registers from EL0 on the
+    /// current core if it uses them. Otherwise, its first access traps with
+    /// `Syndrome::SimdFp`. EL1 access is always granted.
+    pub fn install_fp_access(&self) {
+        let fpen = if self.uses_fp { 0b11 } else { 0b01 };
+        unsafe {
+            let cpacr = CPACR_EL1.get() & !CPACR_EL1::FPEN;
+            CPACR_EL1.set(cpacr | fpen << 20);
+        }
+    }
+
+    /// Returns `true` if the affinity mask of this process allows it to run
+    /// on `core`.
+    pub fn can_run_on(&self, core: usize) -> bool {
+        self.affinity & (1 << core) != 0
+    }
+
     /// Returns `true` if this process is ready to be scheduled.
     ///
     /// This functions returns `true` only if one of the following holds:
@@ -1,65 +1,209 @@
 use alloc::boxed::Box;
 use alloc::collections::vec_deque::VecDeque;
 use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
 
 use aarch64::*;
-use pi::timer;
-use pi::interrupt::{Controller, Interrupt};
+use pi::local_interrupt::{LocalController, LocalInterrupt};
 
 use crate::mutex::Mutex;
-use crate::param::{PAGE_MASK, PAGE_SIZE, TICK, USER_IMG_BASE};
-use crate::process::{Id, Process, State};
+use crate::param::{KERN_STACK_SLOT, NCORES, PAGE_MASK, PAGE_SIZE, TICK, USER_IMG_BASE};
+use crate::process::{thread, Id, Process, State};
 use crate::traps::TrapFrame;
+use crate::vm::asid;
 use crate::VMM;
 use crate::IRQ;
 use crate::SCHEDULER;
 use crate::console::kprintln;
+use crate::shell;
 
 
-/// Process scheduler for the entire machine.
+/// Process scheduler for the entire machine: one run queue per core, each
+/// behind its own lock. Cores with nothing to run steal ready processes from
+/// the other queues.
 #[derive(Debug)]
-pub struct GlobalScheduler(Mutex<Option<Scheduler>>);
+pub struct GlobalScheduler {
+    queues: [Mutex<Option<Scheduler>>; NCORES],
+    /// The ID of the next process to be added.
+    next_id: AtomicU64,
+}
 
 impl GlobalScheduler {
-    /// Returns an uninitialized wrapper around a local scheduler.
+    /// Returns an uninitialized wrapper around the per-core schedulers.
     pub const fn uninitialized() -> GlobalScheduler {
-        GlobalScheduler(Mutex::new(None))
+        GlobalScheduler {
+            queues: [Mutex::new(None), Mutex::new(None), Mutex::new(None), Mutex::new(None)],
+            next_id: AtomicU64::new(0),
+        }
     }
 
     /// Enter a critical region and execute the provided closure with the
-    /// internal scheduler.
+    /// scheduler of the current core.
     pub fn critical<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&mut Scheduler) -> R,
     {
-        let mut guard = self.0.lock();
+        self.critical_on(affinity(), f)
+    }
+
+    /// Enter a critical region and execute the provided closure with the
+    /// scheduler of core `core`.
+    fn critical_on<F, R>(&self, core: usize, f: F) -> R
+    where
+        F: FnOnce(&mut Scheduler) -> R,
+    {
+        let mut guard = self.queues[core].lock();
         f(guard.as_mut().expect("scheduler uninitialized"))
     }
 
+    /// Adds a process to the queue of the least loaded core it may run on,
+    /// and returns that process's ID. The process ID is newly allocated for
+    /// the process and saved in its `trap_frame`. If the process may not run
+    /// on any core, returns `None`.
+    ///
+    /// It is the caller's responsibility to ensure that the first time `switch`
+    /// is called, that process is executing on the CPU.
+    pub fn add(&self, mut process: Process) -> Option<Id> {
+        let core = self.least_loaded(&process)?;
+        let pid = self.next_id.fetch_add(1, Ordering::Relaxed);
+        process.context.tpidr = pid;
+        self.enqueue(core, process);
+        Some(pid)
+    }
+
+    /// Returns the least loaded core `process` may run on, if any.
+    fn least_loaded(&self, process: &Process) -> Option<usize> {
+        (0..NCORES)
+            .filter(|&core| process.can_run_on(core))
+            .min_by_key(|&core| self.critical_on(core, |scheduler| scheduler.processes.len()))
+    }
 
-    /// Adds a process to the scheduler's queue and returns that process's ID.
-    /// For more details, see the documentation on `Scheduler::add()`.
-    pub fn add(&self, process: Process) -> Option<Id> {
-        self.critical(move |scheduler| scheduler.add(process))
+    /// Adds `process` to the queue of core `core`.
+    fn enqueue(&self, core: usize, process: Process) {
+        self.critical_on(core, move |scheduler| scheduler.add(process));
+        // Wake idle cores up: they may steal the process.
+        sev();
+    }
+
+    /// Moves `process`, taken out of the queue of a core it may no longer
+    /// run on, to the queue of the least loaded core it may run on. Its ID is
+    /// kept. The process is dropped if it may not run on any core.
+    fn migrate(&self, process: Process) {
+        match self.least_loaded(&process) {
+            Some(core) => self.enqueue(core, process),
+            None => kprintln!("process {} may not run on any core", process.context.tpidr),
+        }
+    }
+
+    /// Calls `f` with the process whose ID is `pid`, on whichever queue it is,
+    /// and returns its result. Returns `None` if there is no such process.
+    pub fn with_process<F, R>(&self, pid: Id, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Process) -> R,
+    {
+        let mut f = Some(f);
+        (0..NCORES).filter_map(|core| self.critical_on(core, |scheduler| {
+            let process = scheduler.processes.iter_mut().find(|p| p.context.tpidr == pid)?;
+            f.take().map(|f| f(process))
+        })).next()
+    }
+
+    /// Sets the affinity mask of the process whose ID is `pid` to `mask`.
+    /// If the process is queued on a core it may no longer run on, it is
+    /// moved to the queue of one it may run on; if it is running there, it
+    /// is moved when it is next scheduled out. Returns `None` if there is no
+    /// such process.
+    pub fn set_affinity(&self, pid: Id, mask: u64) -> Option<()> {
+        for core in 0..NCORES {
+            let found = self.critical_on(core, |scheduler| {
+                let index = scheduler.processes.iter().position(|p| p.context.tpidr == pid)?;
+                let process = &mut scheduler.processes[index];
+                process.affinity = mask;
+                let running = match process.state {
+                    State::Running => true,
+                    _ => false,
+                };
+                if running || process.can_run_on(core) {
+                    Some(None)
+                } else {
+                    Some(scheduler.processes.remove(index))
+                }
+            });
+            if let Some(moved) = found {
+                if let Some(process) = moved {
+                    self.migrate(process);
+                }
+                return Some(());
+            }
+        }
+        None
+    }
+
+    /// Takes a process that may run on `core` and is ready out of the queue of
+    /// another core, if there is one.
+    fn steal(&self, core: usize) -> Option<Process> {
+        (1..NCORES)
+            .map(|i| (core + i) % NCORES)
+            .filter_map(|victim| self.critical_on(victim, |scheduler| scheduler.steal(core)))
+            .next()
     }
 
     /// Performs a context switch using `tf` by setting the state of the current
     /// process to `new_state`, saving `tf` into the current process, and
     /// restoring the next process's trap frame into `tf`. For more details, see
     /// the documentation on `Scheduler::schedule_out()` and `Scheduler::switch_to()`.
+    ///
+    /// If the current process may no longer run on the current core, it is
+    /// moved to the queue of one it may run on.
     pub fn switch(&self, new_state: State, tf: &mut TrapFrame) -> Id {
-        self.critical(|scheduler| scheduler.schedule_out(new_state, tf));
+        let core = affinity();
+        let misplaced = self.critical(|scheduler| {
+            if !scheduler.schedule_out(new_state, tf) {
+                return None;
+            }
+            match scheduler.processes.back() {
+                Some(p) if !p.can_run_on(core) => scheduler.processes.pop_back(),
+                _ => None,
+            }
+        });
+        if let Some(process) = misplaced {
+            self.migrate(process);
+        }
         self.switch_to(tf)
     }
 
+    /// Switches to the next process that may run on the current core,
+    /// stealing one from another core if its own queue has none. Idles until
+    /// there is one.
     pub fn switch_to(&self, tf: &mut TrapFrame) -> Id {
+        let core = affinity();
         loop {
-            let rtn = self.critical(|scheduler| scheduler.switch_to(tf));
+            let rtn = self.critical(|scheduler| scheduler.switch_to(tf, core));
             if let Some(id) = rtn {
                 return id;
             }
-            // wfe();
-            wfi();
+
+            if let Some(process) = self.steal(core) {
+                self.critical(move |scheduler| scheduler.add(process));
+                continue;
+            }
+
+            // IRQs are masked here, so `tick()` does not run while the core
+            // idles: its timer would stay pending, waking `wfi` up at once,
+            // and the watchdog would go unpetted. Do the tick's work in place.
+            if unsafe { CNTP_CTL_EL0.get() } & CNTP_CTL_EL0::ISTATUS != 0 {
+                rearm();
+            }
+
+            // Core 0 is woken up by its timer interrupt, even with IRQs
+            // masked. The others wait for an event: `add()` and every tick of
+            // core 0 send one.
+            if core == 0 {
+                wfi();
+            } else {
+                wfe();
+            }
         }
     }
 
@@ -70,11 +214,12 @@ impl GlobalScheduler {
         self.critical(|scheduler| scheduler.kill(tf))
     }
 
-    /// Starts executing processes in user space using timer interrupt based
-    /// preemptive scheduling. This method should not return under normal conditions.
+    /// Starts executing processes in user space on the current core, with
+    /// timer interrupt based preemption driven by the core's own physical
+    /// timer. This method should not return under normal conditions.
     pub fn start(&self) -> ! {
-        Controller::new().enable(Interrupt::Timer1);
-        timer::tick_in(TICK);
+        LocalController::new(affinity()).enable(LocalInterrupt::CntPns);
+        tick_in(TICK);
         let mut trap_frame = TrapFrame::default();
         self.switch_to(&mut trap_frame);
         let tf = &trap_frame as *const TrapFrame as u64;
@@ -82,15 +227,21 @@ impl GlobalScheduler {
             asm!("
                 mov SP, $0
                 bl context_restore
-                adr x0, _next
+                // switch to the core's kernel stack:
+                // __stacks_beg + (core + 1) * KERN_STACK_SLOT
+                mrs x0, MPIDR_EL1
+                and x0, x0, #3
+                add x0, x0, #1
+                mov lr, $1
+                mul x0, x0, lr
+                adrp lr, __stacks_beg
+                add lr, lr, :lo12:__stacks_beg
+                add x0, x0, lr
                 mov SP, x0
                 mov x0, xzr
                 mov lr, xzr
-                eret
-
-                _next:
-                    .balign 0x10000"
-                :: "r"(tf)
+                eret"
+                :: "r"(tf), "i"(KERN_STACK_SLOT)
                 :: "volatile");
         }
         loop {} // infinite loop
@@ -98,12 +249,11 @@ impl GlobalScheduler {
 
     /// Initializes the scheduler and add userspace processes to the Scheduler
     pub unsafe fn initialize(&self) {
-        *self.0.lock() = Some(Scheduler::new());
-        IRQ.register(Interrupt::Timer1, Box::new(|tf: &mut TrapFrame| {
-            timer::tick_in(TICK);
-            // kprintln!("tick");
-            SCHEDULER.switch(State::Ready, tf);
-        }));
+        for queue in self.queues.iter() {
+            *queue.lock() = Some(Scheduler::new());
+        }
+        IRQ.register_local(LocalInterrupt::CntPns, tick);
+        thread::spawn(|| shell::shell("$")).expect("failed to spawn the shell");
         self.add(Process::load("/sleep").unwrap());
         self.add(Process::load("/fib").unwrap());
         self.add(Process::load("/sleep").unwrap());
@@ -130,10 +280,39 @@ impl GlobalScheduler {
     // }
 }
 
+/// Sets up the physical timer of the current core to interrupt `t` duration
+/// from now. The interrupt is raised if `LocalInterrupt::CntPns` is enabled
+/// for the core.
+fn tick_in(t: Duration) {
+    unsafe {
+        let ticks = CNTFRQ_EL0.get() * t.as_micros() as u64 / 1_000_000;
+        CNTP_TVAL_EL0.set(ticks);
+        CNTP_CTL_EL0.set(CNTP_CTL_EL0::ENABLE);
+    }
+}
+
+/// Sets up the next tick of the current core. On core 0, also pets the
+/// watchdog and wakes the idle cores up.
+fn rearm() {
+    tick_in(TICK);
+    if affinity() == 0 {
+        crate::watchdog::pet();
+        // Idle cores check for processes done waiting.
+        sev();
+    }
+}
+
+/// Handles the timer interrupt of a core: preempts the running process.
+fn tick(tf: &mut TrapFrame) {
+    rearm();
+    // kprintln!("tick");
+    SCHEDULER.switch(State::Ready, tf);
+}
+
+/// The run queue of a core.
 #[derive(Debug)]
 pub struct Scheduler {
     processes: VecDeque<Process>,
-    last_id: Option<Id>,
 }
 
 impl Scheduler {
@@ -141,23 +320,34 @@ impl Scheduler {
     fn new() -> Scheduler {
         Scheduler {
             processes: VecDeque::new(),
-            last_id: None,
         }
     }
 
-    /// Adds a process to the scheduler's queue and returns that process's ID if
-    /// a new process can be scheduled. The process ID is newly allocated for
-    /// the process and saved in its `trap_frame`. If no further processes can
-    /// be scheduled, returns `None`.
-    ///
-    /// It is the caller's responsibility to ensure that the first time `switch`
-    /// is called, that process is executing on the CPU.
-    fn add(&mut self, mut process: Process) -> Option<Id> {
-        let pid = self.last_id.and_then(|x| x.checked_add(1)).unwrap_or_default();
-        process.context.tpidr = pid;
+    /// Adds a process to the end of the queue.
+    fn add(&mut self, process: Process) {
         self.processes.push_back(process);
-        self.last_id = Some(pid);
-        self.last_id
+    }
+
+    /// Removes and returns the process waiting the longest that may run on
+    /// `core` and is ready, if any.
+    fn steal(&mut self, core: usize) -> Option<Process> {
+        let index = self.processes.iter_mut().position(|p| p.can_run_on(core) && p.is_ready())?;
+        self.processes.remove(index)
+    }
+
+    /// Returns the index of the process running on the current core, whose
+    /// ID is in `tf`. Several cores may be running processes at once.
+    fn position(&self, tf: &TrapFrame) -> Option<usize> {
+        self.processes.iter().position(|p| match p.state {
+            State::Running => p.context.tpidr == tf.tpidr,
+            _ => false,
+        })
+    }
+
+    /// Returns the process running on the current core, whose ID is in `tf`.
+    pub fn current(&mut self, tf: &TrapFrame) -> Option<&mut Process> {
+        let index = self.position(tf)?;
+        self.processes.get_mut(index)
     }
 
     /// Finds the currently running process, sets the current process's state
@@ -168,38 +358,35 @@ impl Scheduler {
     /// If the `processes` queue is empty or there is no current process,
     /// returns `false`. Otherwise, returns `true`.
     fn schedule_out(&mut self, new_state: State, tf: &mut TrapFrame) -> bool {
-        match self.processes.pop_front() {
+        match self.position(tf).and_then(|index| self.processes.remove(index)) {
             Some(mut p) => {
-                match p.state {
-                    State::Running => {
-                        p.state = new_state;
-                        p.context = Box::new(*tf);
-                        self.processes.push_back(p);
-                        return true                        
-                    },
-                    _ => return false
-                }
+                p.state = new_state;
+                p.context = Box::new(*tf);
+                self.processes.push_back(p);
+                true
             },
-            _ => false
+            None => false
         }
     }
 
-    /// Finds the next process to switch to, brings the next process to the
-    /// front of the `processes` queue, changes the next process's state to
-    /// `Running`, and performs context switch by restoring the next process`s
-    /// trap frame into `tf`.
+    /// Finds the next process that may run on `core` to switch to, brings
+    /// the next process to the front of the `processes` queue, changes the
+    /// next process's state to `Running`, and performs context switch by
+    /// restoring the next process`s trap frame into `tf`.
     ///
     /// If there is no process to switch to, returns `None`. Otherwise, returns
     /// `Some` of the next process`s process ID.
-    fn switch_to(&mut self, tf: &mut TrapFrame) -> Option<Id> {
+    fn switch_to(&mut self, tf: &mut TrapFrame, core: usize) -> Option<Id> {
         let index = self.processes.iter_mut().position(|item: &mut Process| -> bool {
-            item.is_ready()
+            item.can_run_on(core) && item.is_ready()
         })?;
         // kprintln!("removing {}", index);
         let mut next = self.processes.remove(index)?;
         let next_pid = next.context.tpidr;
         // kprintln!("next: {}", next_pid);
         next.state = State::Running;
+        next.context.ttbr1 = asid::ttbr(&mut next.asid, next.vmap.get_baddr());
+        next.install_fp_access();
         *tf = *next.context;
         self.processes.push_front(next);
         Some(next_pid)
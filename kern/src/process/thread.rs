@@ -0,0 +1,75 @@
+use alloc::boxed::Box;
+use core::time::Duration;
+
+use aarch64::{sp_sel, SPSR_EL1};
+use kernel_api::syscall;
+use kernel_api::{OsError, OsResult};
+
+use crate::process::{Id, Process};
+use crate::traps::TrapFrame;
+use crate::SCHEDULER;
+
+/// The system call kernel threads block with (see `wait_until()`). It comes
+/// after those of `kernel_api`, and is refused to user processes.
+pub const NR_WAIT: usize = 0x100;
+
+/// The body of a kernel thread.
+pub type ThreadFn = Box<dyn FnOnce() + Send>;
+
+/// Spawns a kernel thread running `f`, scheduled like any other process, and
+/// returns its process ID.
+///
+/// Kernel threads are scheduled cooperatively: they run with IRQs masked, so
+/// they are never preempted and may take any kernel lock. They give the core
+/// up by calling `yield_now()`, `sleep()` or `wait_until()`, and exit when
+/// `f` returns. A thread waiting for an event must block in `wait_until()`
+/// rather than poll for it.
+pub fn spawn<F: FnOnce() + Send + 'static>(f: F) -> OsResult<Id> {
+    let thread = Process::kernel_thread(Box::new(f))?;
+    SCHEDULER.add(thread).ok_or(OsError::InvalidArgument)
+}
+
+/// Returns `true` if the caller is running in a kernel thread, rather than in
+/// an exception handler.
+pub fn in_thread() -> bool {
+    sp_sel() == 0
+}
+
+/// Returns `true` if `tf` is the context of a kernel thread, which runs at
+/// EL1, rather than that of a user process.
+pub fn is_kernel_thread(tf: &TrapFrame) -> bool {
+    tf.spsr & SPSR_EL1::M != 0
+}
+
+/// Lets the other processes ready to run on this core go first.
+pub fn yield_now() {
+    sleep(Duration::from_millis(0));
+}
+
+/// Puts the calling kernel thread to sleep for at least `span`.
+pub fn sleep(span: Duration) {
+    let _ = syscall::sleep(span);
+}
+
+/// Blocks the calling kernel thread until `ready` returns `true`. The core
+/// runs other processes, or idles, meanwhile.
+///
+/// The scheduler calls `ready` with IRQs masked and a run queue locked: it
+/// must neither block nor take the lock of a run queue.
+pub fn wait_until(ready: fn() -> bool) {
+    unsafe {
+        asm!("mov x0, $0
+              svc $1"
+             :
+             : "r"(ready as usize), "i"(NR_WAIT)
+             : "x0", "x7"
+             : "volatile");
+    }
+}
+
+/// The entry point of kernel threads: runs the body `f` points to, then exits.
+pub extern "C" fn start(f: *mut ThreadFn) -> ! {
+    let f = unsafe { *Box::from_raw(f) };
+    f();
+    syscall::exit()
+}
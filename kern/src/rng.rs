@@ -0,0 +1,101 @@
+mod chacha;
+
+#[cfg(test)]
+mod tests;
+
+use core::time::Duration;
+
+use pi::rng::Rng;
+use pi::timer;
+
+use crate::mutex::Mutex;
+
+pub use self::chacha::ChaCha20Rng;
+
+/// How long to wait for the hardware generator to produce its first word.
+/// It is absent under QEMU.
+const WARMUP_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// The number of hardware words the generator is seeded with.
+const HARDWARE_SEED_WORDS: usize = 16;
+
+/// The number of timer jitter samples the generator is seeded with.
+const JITTER_SAMPLES: usize = 64;
+
+/// The kernel's random number generator: a CSPRNG seeded from the hardware
+/// generator, when there is one, and from timer jitter.
+struct Generator {
+    cipher: ChaCha20Rng,
+    hardware: Option<Rng>,
+}
+
+static GENERATOR: Mutex<Generator> = Mutex::new(Generator {
+    cipher: ChaCha20Rng::new(),
+    hardware: None,
+});
+
+/// Returns samples of timer jitter: the number of spins between two ticks of
+/// the microsecond timer varies with caches, DRAM refresh and bus traffic.
+fn jitter() -> [u32; JITTER_SAMPLES] {
+    let mut samples = [0; JITTER_SAMPLES];
+    for sample in samples.iter_mut() {
+        let start = timer::current_time();
+        let mut spins = 0u32;
+        while timer::current_time() == start {
+            spins = spins.wrapping_add(1);
+        }
+        *sample = spins.rotate_left(16) ^ start.subsec_nanos();
+    }
+    samples
+}
+
+/// Seeds the generator. Until this function is called, its output is
+/// predictable.
+pub fn initialize() {
+    let mut rng = Rng::new();
+    let mut seed = [0; HARDWARE_SEED_WORDS];
+    let hardware = match rng.next_u32_timeout(WARMUP_TIMEOUT) {
+        Some(first) => {
+            seed[0] = first;
+            for word in seed[1..].iter_mut() {
+                *word = rng.next_u32();
+            }
+            true
+        }
+        None => false,
+    };
+
+    let mut generator = GENERATOR.lock();
+    generator.cipher.reseed(&seed);
+    generator.cipher.reseed(&jitter());
+    if hardware {
+        generator.hardware = Some(rng);
+    }
+}
+
+/// Returns `true` if the generator draws from the hardware generator.
+pub fn is_hardware_backed() -> bool {
+    GENERATOR.lock().hardware.is_some()
+}
+
+/// Fills `buf` with random bytes.
+pub fn fill(buf: &mut [u8]) {
+    let mut generator = GENERATOR.lock();
+
+    // Fold fresh entropy in on every request: the time, and a hardware word
+    // if one is ready.
+    let now = timer::current_time();
+    let mut fresh = [now.subsec_nanos(), now.as_secs() as u32, 0];
+    if let Some(word) = generator.hardware.as_mut().and_then(|rng| rng.try_next_u32()) {
+        fresh[2] = word;
+    }
+    generator.cipher.reseed(&fresh);
+    generator.cipher.fill_bytes(buf);
+}
+
+/// Returns a random `u64`.
+pub fn next_u64() -> u64 {
+    let mut bytes = [0; 8];
+    fill(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
@@ -0,0 +1,92 @@
+/// The ChaCha constant words, "expand 32-byte k".
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// Returns the ChaCha20 block (RFC 7539) for `key`, where `input` holds the
+/// last four state words: the block counter and the nonce.
+pub fn block(key: &[u32; 8], input: &[u32; 4]) -> [u8; 64] {
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&CONSTANTS);
+    initial[4..12].copy_from_slice(key);
+    initial[12..].copy_from_slice(input);
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; 64];
+    for (i, chunk) in out.chunks_mut(4).enumerate() {
+        chunk.copy_from_slice(&state[i].wrapping_add(initial[i]).to_le_bytes());
+    }
+    out
+}
+
+/// A cryptographically secure generator producing the ChaCha20 keystream.
+///
+/// The key is replaced by fresh keystream after every request ("fast key
+/// erasure"), so that earlier output cannot be recovered from the state.
+pub struct ChaCha20Rng {
+    key: [u32; 8],
+    counter: u64,
+}
+
+impl ChaCha20Rng {
+    /// Returns an unseeded generator. Its output is predictable until
+    /// `reseed()` is called with enough entropy.
+    pub const fn new() -> ChaCha20Rng {
+        ChaCha20Rng { key: [0; 8], counter: 0 }
+    }
+
+    /// Returns the next block of keystream.
+    fn next_block(&mut self) -> [u8; 64] {
+        let input = [self.counter as u32, (self.counter >> 32) as u32, 0, 0];
+        self.counter = self.counter.wrapping_add(1);
+        block(&self.key, &input)
+    }
+
+    /// Replaces the key by the next 32 bytes of keystream.
+    fn rekey(&mut self) {
+        let block = self.next_block();
+        for (word, bytes) in self.key.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+    }
+
+    /// Mixes `entropy` into the key. Entropy only adds to the state: mixing
+    /// in predictable words never weakens the generator.
+    pub fn reseed(&mut self, entropy: &[u32]) {
+        for words in entropy.chunks(8) {
+            for (word, &extra) in self.key.iter_mut().zip(words) {
+                *word ^= extra;
+            }
+            self.rekey();
+        }
+    }
+
+    /// Fills `buf` with random bytes.
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(64) {
+            let block = self.next_block();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        self.rekey();
+    }
+}
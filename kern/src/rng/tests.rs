@@ -0,0 +1,41 @@
+use super::chacha::{block, ChaCha20Rng};
+
+#[test]
+fn test_chacha20_block() {
+    // RFC 7539, section 2.3.2
+    let mut key = [0u32; 8];
+    for (i, word) in key.iter_mut().enumerate() {
+        let b = 4 * i as u32;
+        *word = b | (b + 1) << 8 | (b + 2) << 16 | (b + 3) << 24;
+    }
+    let input = [1, 0x0900_0000, 0x4a00_0000, 0];
+
+    let expected: [u8; 64] = [
+        0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20, 0x71, 0xc4,
+        0xc7, 0xd1, 0xf4, 0xc7, 0x33, 0xc0, 0x68, 0x03, 0x04, 0x22, 0xaa, 0x9a, 0xc3, 0xd4, 0x6c, 0x4e,
+        0xd2, 0x82, 0x64, 0x46, 0x07, 0x9f, 0xaa, 0x09, 0x14, 0xc2, 0xd7, 0x05, 0xd9, 0x8b, 0x02, 0xa2,
+        0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9, 0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c, 0x4e,
+    ];
+    assert_eq!(&block(&key, &input)[..], &expected[..]);
+}
+
+#[test]
+fn test_chacha20_rng_key_erasure() {
+    let mut rng = ChaCha20Rng::new();
+    rng.reseed(&[0xdead_beef, 42]);
+
+    let (mut first, mut second) = ([0u8; 100], [0u8; 100]);
+    rng.fill_bytes(&mut first);
+    rng.fill_bytes(&mut second);
+    assert_ne!(&first[..], &second[..]);
+
+    let mut other = ChaCha20Rng::new();
+    other.reseed(&[0xdead_beef, 42]);
+    let mut again = [0u8; 100];
+    other.fill_bytes(&mut again);
+    assert_eq!(&first[..], &again[..]);
+
+    other.reseed(&[1]);
+    other.fill_bytes(&mut again);
+    assert_ne!(&second[..], &again[..]);
+}
@@ -12,9 +12,14 @@ use pi::atags::Atags;
 use fat32::traits::FileSystem;
 use fat32::traits::{Dir, Entry, Metadata, Timestamp};
 
-use crate::console::{kprint, kprintln, CONSOLE};
+use crate::bench;
+use crate::console::{kprint, kprintln, rx, CONSOLE};
+use crate::process::thread;
+use crate::vm::asid;
+use crate::watchdog;
 use crate::ALLOCATOR;
 use crate::FILESYSTEM;
+use crate::VMM;
 
 /// Error type for `Command` parse failures.
 #[derive(Debug)]
@@ -205,6 +210,145 @@ fn sleep(ms: &str) {
     }
 }
 
+fn meminfo() {
+    kprint!("{}", ALLOCATOR.stats());
+
+    kprintln!("{:>10} {:>10} {:>10} {:>10}", "object", "in use", "slabs", "released");
+    ALLOCATOR.for_each_cache(|cache| {
+        kprintln!("{:>10} {:>10} {:>10} {:>10}",
+            cache.object_size, cache.objects_in_use, cache.slabs, cache.slabs_released);
+    });
+
+    #[cfg(feature = "heap-trace")]
+    {
+        use crate::allocator::trace::OTHER;
+        use crate::ksyms::{self, Demangle};
+
+        kprintln!("{:>10} {:>10}  {}", "live", "bytes", "call site");
+        let untraced = ALLOCATOR.for_each_site(|summary| {
+            kprint!("{:>10} {:>10}  ", summary.count, summary.bytes);
+            if summary.site == OTHER {
+                kprintln!("other");
+            } else if let Some((name, offset)) = ksyms::lookup(summary.site) {
+                kprintln!("{}+{:#x}", Demangle(name), offset);
+            } else {
+                kprintln!("{:#010x}", summary.site);
+            }
+        });
+        if untraced != 0 {
+            kprintln!("{} live allocations were not traced", untraced);
+        }
+    }
+}
+
+fn board() {
+    use pi::mailbox::{self, Clock};
+
+    // The whole report is read from the firmware at once.
+    crate::mailbox::with(|| {
+        match mailbox::board_revision() {
+            Ok(revision) => kprintln!("revision:    {:#x}", revision),
+            Err(e) => kprintln!("revision:    {:?}", e),
+        }
+        match mailbox::board_serial() {
+            Ok(serial) => kprintln!("serial:      {:016x}", serial),
+            Err(e) => kprintln!("serial:      {:?}", e),
+        }
+        match mailbox::firmware_revision() {
+            Ok(revision) => kprintln!("firmware:    {:#x}", revision),
+            Err(e) => kprintln!("firmware:    {:?}", e),
+        }
+        match (mailbox::arm_memory(), mailbox::vc_memory()) {
+            (Ok((arm_base, arm_size)), Ok((vc_base, vc_size))) => {
+                kprintln!("arm memory:  {:#010x} ({} MiB)", arm_base, arm_size >> 20);
+                kprintln!("vc memory:   {:#010x} ({} MiB)", vc_base, vc_size >> 20);
+            }
+            (Err(e), _) | (_, Err(e)) => kprintln!("memory:      {:?}", e),
+        }
+        let clocks = [(Clock::Arm, "arm"), (Clock::Core, "core"), (Clock::Emmc, "emmc"), (Clock::Uart, "uart")];
+        for &(clock, name) in clocks.iter() {
+            match (mailbox::clock_rate(clock), mailbox::max_clock_rate(clock)) {
+                (Ok(rate), Ok(max)) => kprintln!("{:<5} clock: {} MHz (max {} MHz)",
+                    name, rate / 1_000_000, max / 1_000_000),
+                (Err(e), _) | (_, Err(e)) => kprintln!("{:<5} clock: {:?}", name, e),
+            }
+        }
+        match (mailbox::temperature(), mailbox::max_temperature()) {
+            (Ok(temp), Ok(max)) => kprintln!("temperature: {}.{:03} C (max {}.{:03} C)",
+                temp / 1000, temp % 1000, max / 1000, max % 1000),
+            (Err(e), _) | (_, Err(e)) => kprintln!("temperature: {:?}", e),
+        }
+        kprintln!("last reset:  {:?}", pi::pm::reset_reason());
+    });
+}
+
+fn vmdump() {
+    kprintln!("{:<25} {:<13} {}", "virtual", "physical", "attributes");
+    VMM.for_each_mapping(|mapping| kprintln!("{:?}", mapping));
+}
+
+fn bench(args: &[&str]) {
+    use core::time::Duration;
+    let switches = match args.get(0).map(|n| n.parse::<usize>()) {
+        None => 10000,
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => {
+            kprintln!("incorrect usage, please use: bench [switches]");
+            return;
+        }
+    };
+
+    let cost = bench::context_switch(switches);
+    let per_switch = |total: Duration| total.as_nanos() / cost.switches as u128;
+    kprintln!("{} address space switches", cost.switches);
+    kprintln!("  flushed: {:?} ({} ns/switch)", cost.flushed, per_switch(cost.flushed));
+    kprintln!("  tagged:  {:?} ({} ns/switch)", cost.tagged, per_switch(cost.tagged));
+    kprintln!("ASID rollovers: {}", asid::rollovers());
+}
+
+fn watchdog_cmd(args: &[&str]) {
+    use core::time::Duration;
+    match args.get(0).map(|secs| (*secs, secs.parse::<u64>())) {
+        None => {}
+        Some(("off", _)) => watchdog::disarm(),
+        Some((_, Ok(secs))) if secs > 0 => watchdog::arm(Duration::from_secs(secs)),
+        Some(_) => {
+            kprintln!("incorrect usage, please use: watchdog [seconds|off]");
+            return;
+        }
+    }
+    match watchdog::remaining() {
+        Some(remaining) => kprintln!("watchdog armed, {:?} left", remaining),
+        None => kprintln!("watchdog disarmed"),
+    }
+}
+
+fn debug() {
+    if cfg!(feature = "gdbstub") {
+        aarch64::brk!(0);
+    } else {
+        kprintln!("the GDB stub is not built in: enable the `gdbstub` feature");
+    }
+}
+
+/// Reads a byte from the console. In a kernel thread, the thread blocks
+/// until a byte has been received. Once reception is interrupt-driven, the
+/// console is not held meanwhile.
+fn read_byte() -> u8 {
+    if !rx::is_enabled() {
+        return CONSOLE.lock().read_byte();
+    }
+    if !thread::in_thread() {
+        return rx::read_byte();
+    }
+    loop {
+        if let Some(byte) = rx::try_read_byte() {
+            return byte;
+        }
+        thread::wait_until(rx::has_byte);
+    }
+}
+
 /// Starts a shell using `prefix` as the prefix for each line. This function
 /// returns if the `exit` command is called.
 const BACKSPACE: u8 = 8;
@@ -233,7 +377,7 @@ pub fn shell(prefix: &str) -> ! {
         kprint!("{} ", prefix);
     
         loop { // loop until they exit
-            let byte = CONSOLE.lock().read_byte();
+            let byte = read_byte();
             if byte == b'\r' || byte == b'\n' {
                 let cmd: &str = match core::str::from_utf8(&buf.as_slice()) {
                     Ok(s) => s,
@@ -262,6 +406,14 @@ pub fn shell(prefix: &str) -> ! {
                     "ls" => ls(&command.args[1..], &working_directory),
                     "cat" => cat(&command.args[1..], &working_directory),
                     "sleep" => sleep(&command.args[1]),
+                    "meminfo" => meminfo(),
+                    "board" => board(),
+                    "vmdump" => vmdump(),
+                    "bench" => bench(&command.args[1..]),
+                    "watchdog" => watchdog_cmd(&command.args[1..]),
+                    "debug" => debug(),
+                    "reboot" => pi::pm::reboot(),
+                    "halt" => pi::pm::halt(),
                     _ =>  kprint!("\nunknown command: {}", command.path()),
                 }
                 break
@@ -0,0 +1,97 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use pi::interrupt::{Controller, Interrupt};
+use pi::spi::{Spi, Transfer};
+
+use crate::mutex::Mutex;
+use crate::process::thread;
+use crate::IRQ;
+
+/// `SPI0`, driven by the `Interrupt::Spi` handler.
+struct Bus {
+    spi: Spi,
+    /// The transfer in progress, if any.
+    transfer: Option<Transfer<Vec<u8>>>,
+    /// The buffer of the last transfer, once done, until its caller takes it.
+    finished: Option<Vec<u8>>,
+}
+
+impl Bus {
+    /// Advances the transfer in progress, if any.
+    fn service(&mut self) {
+        let done = match self.transfer.as_mut() {
+            Some(transfer) => self.spi.service(transfer),
+            None => return,
+        };
+        if done {
+            self.finished = self.transfer.take().map(Transfer::into_inner);
+        }
+    }
+}
+
+static BUS: Mutex<Option<Bus>> = Mutex::new(None);
+
+/// Initializes `SPI0` with its defaults (see `Spi::new()`) and registers the
+/// handler driving its transfers.
+///
+/// This is only done on the first transfer: `Spi::new()` switches GPIO 7-11
+/// to the controller, and boards not using SPI may use them otherwise.
+fn initialize() -> Bus {
+    let bus = Bus { spi: Spi::new(), transfer: None, finished: None };
+    IRQ.register(Interrupt::Spi, Box::new(|_| {
+        if let Some(bus) = BUS.lock().as_mut() {
+            bus.service();
+        }
+    }));
+    Controller::new().enable(Interrupt::Spi);
+    bus
+}
+
+/// Calls `f` with the bus, initializing it as needed. IRQs are masked so
+/// that the handler cannot preempt the holder of `BUS` on this core.
+fn with_bus<F: FnOnce(&mut Bus) -> R, R>(f: F) -> R {
+    let mut bus = BUS.lock_irq();
+    if bus.is_none() {
+        *bus = Some(initialize());
+    }
+    f(bus.as_mut().unwrap())
+}
+
+/// Returns `true` if no transfer is in progress or waiting to be taken.
+fn is_idle() -> bool {
+    with_bus(|bus| bus.transfer.is_none() && bus.finished.is_none())
+}
+
+/// Returns `true` if the last transfer is done.
+fn is_finished() -> bool {
+    with_bus(|bus| bus.finished.is_some())
+}
+
+/// Sends the bytes of `buf` and returns the bytes received meanwhile, as
+/// many. The calling kernel thread blocks until the bus is free, then until
+/// the transfer is done, instead of polling the controller.
+///
+/// Only kernel threads may call this function; elsewhere, use the blocking
+/// `Spi::transfer()`.
+pub fn transfer(buf: Vec<u8>) -> Vec<u8> {
+    let mut buf = Some(buf);
+    while buf.is_some() {
+        with_bus(|bus| {
+            if bus.transfer.is_none() && bus.finished.is_none() {
+                bus.transfer = Some(bus.spi.start(buf.take().unwrap()));
+                bus.service();
+            }
+        });
+        if buf.is_some() {
+            thread::wait_until(is_idle);
+        }
+    }
+
+    loop {
+        if let Some(buf) = with_bus(|bus| bus.finished.take()) {
+            return buf;
+        }
+        thread::wait_until(is_finished);
+    }
+}
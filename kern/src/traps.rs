@@ -2,13 +2,21 @@ mod frame;
 mod syndrome;
 mod syscall;
 
+use crate::console::kprintln;
+use crate::gdbstub;
+use crate::param::NCORES;
 use crate::IRQ;
+use crate::SCHEDULER;
 use crate::shell;
 
 pub mod irq;
 pub use self::frame::TrapFrame;
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aarch64::{affinity, FAR_EL1};
 use pi::interrupt::{Controller, Interrupt};
+use pi::local_interrupt::{LocalController, LocalInterrupt};
 
 use self::syndrome::Syndrome;
 use self::syscall::handle_syscall;
@@ -38,33 +46,110 @@ pub struct Info {
     kind: Kind,
 }
 
+/// The trap frame of the exception being handled on each core, or 0.
+static ACTIVE_FRAMES: [AtomicUsize; NCORES] =
+    [AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)];
+
+/// Returns the trap frame of the exception being handled on the current core,
+/// if any. Meant for post-mortem dumps: the frame may be modified under the
+/// returned reference.
+pub fn active_frame() -> Option<&'static TrapFrame> {
+    let frame = ACTIVE_FRAMES[affinity()].load(Ordering::Relaxed);
+    unsafe { (frame as *const TrapFrame).as_ref() }
+}
+
 /// This function is called when an exception occurs. The `info` parameter
 /// specifies the source and kind of exception that has occurred. The `esr` is
 /// the value of the exception syndrome register. Finally, `tf` is a pointer to
 /// the trap frame for the exception.
 #[no_mangle]
 pub extern "C" fn handle_exception(info: Info, esr: u32, tf: &mut TrapFrame) {
-    use crate::shell;
- 
-    match info.kind {
-        Kind::Synchronous => {
-            match Syndrome::from(esr) {
-                Syndrome::Brk(_) => {
-                    shell::shell("$");
-                    tf.elr += 4;
-                },
-                Syndrome::Svc(s) => handle_syscall(s, tf),
-                _ => ()
-            }
+    let outer = ACTIVE_FRAMES[affinity()].swap(tf as *mut TrapFrame as usize, Ordering::Relaxed);
+    dispatch(info, esr, tf);
+    ACTIVE_FRAMES[affinity()].store(outer, Ordering::Relaxed);
+}
+
+fn dispatch(info: Info, esr: u32, tf: &mut TrapFrame) {
+    match (info.source, info.kind) {
+        // Processes are only ever started in AArch64.
+        (Source::LowerAArch32, _) => reject(info, esr, tf),
+        (_, Kind::Synchronous) => handle_synchronous(info, esr, tf),
+        (_, Kind::Irq) => handle_irq(tf),
+        (_, Kind::Fiq) => IRQ.invoke_fiq(tf),
+        (_, Kind::SError) => handle_serror(info, esr, tf),
+    }
+}
+
+fn handle_synchronous(info: Info, esr: u32, tf: &mut TrapFrame) {
+    match Syndrome::from(esr) {
+        Syndrome::Brk(_) if cfg!(feature = "gdbstub") => gdbstub::handle_brk(tf),
+        Syndrome::Brk(_) => {
+            shell::shell("$");
+            tf.elr += 4;
         },
-        Kind::Irq => {
-            let controller = Controller::new();
-            for i in Interrupt::iter() {
-                if controller.is_pending(*i) { 
-                    IRQ.invoke(*i, tf); 
+        Syndrome::Step if cfg!(feature = "gdbstub") => gdbstub::handle_step(tf),
+        Syndrome::Svc(s) => handle_syscall(s, tf),
+        Syndrome::SimdFp if info.source == Source::LowerAArch64 => {
+            // First FP/SIMD access of the process: the registers join
+            // its context from now on, starting out zeroed.
+            SCHEDULER.critical(|scheduler| {
+                if let Some(process) = scheduler.current(tf) {
+                    process.uses_fp = true;
+                    process.install_fp_access();
                 }
+            });
+            tf.q = [0; 32];
+            tf.fpcr = 0;
+            tf.fpsr = 0;
+        }
+        _ => reject(info, esr, tf),
+    }
+}
+
+fn handle_irq(tf: &mut TrapFrame) {
+    let local = LocalController::new(affinity());
+    for i in LocalInterrupt::iter() {
+        if local.is_pending(*i) {
+            IRQ.invoke_local(*i, tf);
+        }
+    }
+
+    if local.is_gpu_pending() {
+        let controller = Controller::new();
+        for i in Interrupt::iter() {
+            if controller.is_pending(*i) { 
+                IRQ.invoke(*i, tf); 
             }
-        },
-        _ => ()
+        }
+    }
+}
+
+/// Logs an SError and resumes the interrupted context. SErrors are
+/// asynchronous: they are reported once the access that caused them, usually
+/// a bus error on a peripheral, has long retired, so there is nothing to
+/// retry or blame.
+fn handle_serror(info: Info, esr: u32, tf: &mut TrapFrame) {
+    kprintln!("SError on core {} from {:?} (esr {:#010x}, elr {:#x}): resuming",
+        affinity(), info.source, esr, tf.elr);
+}
+
+/// Rejects an exception the kernel does not handle, with a dump of its
+/// decoded syndrome. The process that took it is killed; if the kernel took
+/// it itself, retrying would fault forever: it panics.
+fn reject(info: Info, esr: u32, tf: &mut TrapFrame) {
+    let far = unsafe { FAR_EL1.get() };
+    match info.source {
+        Source::CurrentSpEl0 | Source::CurrentSpElx => {
+            panic!("unexpected {:?} exception from {:?}: {:?} \
+                (esr {:#010x}, elr {:#x}, far {:#x}, spsr {:#x})",
+                info.kind, info.source, Syndrome::from(esr), esr, tf.elr, far, tf.spsr);
+        }
+        Source::LowerAArch64 | Source::LowerAArch32 => {
+            kprintln!("killing process {}: unexpected {:?} exception from {:?}: {:?} \
+                (esr {:#010x}, elr {:#x}, far {:#x}, spsr {:#x})",
+                tf.tpidr, info.kind, info.source, Syndrome::from(esr), esr, tf.elr, far, tf.spsr);
+            let _ = SCHEDULER.kill(tf);
+            SCHEDULER.switch_to(tf);
+        }
     }
 }
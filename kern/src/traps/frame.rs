@@ -10,6 +10,8 @@ pub struct TrapFrame {
     pub sp:    u64,
     pub spsr:  u64,
     pub elr:   u64,
+    pub fpcr:  u64,
+    pub fpsr:  u64,
     pub q: [u128; 32],
     pub x: [u64; 32],
 }
@@ -1,38 +1,129 @@
 use alloc::boxed::Box;
-use pi::interrupt::Interrupt;
+use alloc::vec::Vec;
+use pi::gpio;
+use pi::interrupt::{Controller, Interrupt};
+use pi::local_interrupt::LocalInterrupt;
 
+use crate::console::kprintln;
 use crate::mutex::Mutex;
 use crate::traps::TrapFrame;
 
 pub type IrqHandler = Box<dyn FnMut(&mut TrapFrame) + Send>;
 pub type IrqHandlers = [Option<IrqHandler>; Interrupt::MAX];
 
-pub struct Irq(Mutex<Option<IrqHandlers>>);
+/// Per-pin handlers for GPIO events, indexed by pin number.
+pub type GpioHandlers = Vec<Option<IrqHandler>>;
+
+/// A handler for a local interrupt. Every core has its own copy of the local
+/// interrupts, so the handler may run on several cores at once: it is called
+/// without holding any lock, and cannot carry state.
+pub type LocalIrqHandler = fn(&mut TrapFrame);
+pub type LocalIrqHandlers = [Option<LocalIrqHandler>; LocalInterrupt::MAX];
+
+/// The interrupts raised by the GPIO banks: pins 0-27, 28-45 and 46-53.
+/// `Gpio3` is raised for any pin and is left unused.
+const GPIO_INTERRUPTS: [Interrupt; 3] = [Interrupt::Gpio0, Interrupt::Gpio1, Interrupt::Gpio2];
+
+pub struct Irq {
+    handlers: Mutex<Option<IrqHandlers>>,
+    gpio: Mutex<Option<GpioHandlers>>,
+    local: Mutex<LocalIrqHandlers>,
+    fiq: Mutex<Option<IrqHandler>>,
+}
 
 impl Irq {
     pub const fn uninitialized() -> Irq {
-        Irq(Mutex::new(None))
+        Irq {
+            handlers: Mutex::new(None),
+            gpio: Mutex::new(None),
+            local: Mutex::new([None; LocalInterrupt::MAX]),
+            fiq: Mutex::new(None),
+        }
     }
 
     pub fn initialize(&self) {
-        *self.0.lock() = Some([None, None, None, None, None, None, None, None]);
+        *self.handlers.lock() = Some([None, None, None, None, None, None, None, None, None, None]);
+        *self.gpio.lock() = Some((0..gpio::NUM_PINS).map(|_| None).collect());
+        for &int in GPIO_INTERRUPTS.iter() {
+            self.register(int, Box::new(|tf| crate::IRQ.invoke_gpio(tf)));
+        }
     }
 
     /// Register an irq handler for an interrupt.
     /// The caller should assure that `initialize()` has been called before calling this function.
     pub fn register(&self, int: Interrupt, handler: IrqHandler) {
         let index = Interrupt::to_index(int);
-        match &mut *self.0.lock() {
+        match &mut *self.handlers.lock() {
             Some(irq_handlers) => irq_handlers[index] = Some(handler),
             None => panic!("Unable to access irq handlers")
         }
     }
 
+    /// Register a handler for the events detected on GPIO pin `pin`, and
+    /// enable the GPIO interrupts. The events to detect are configured on the
+    /// pin itself, with `Gpio::enable_event()`.
+    ///
+    /// The event is cleared before `handler` runs. A handler for a level event
+    /// must disable it or change the level, or it is invoked again right away.
+    /// The caller should assure that `initialize()` has been called before calling this function.
+    pub fn register_gpio(&self, pin: u8, handler: IrqHandler) {
+        match &mut *self.gpio.lock() {
+            Some(gpio_handlers) => gpio_handlers[pin as usize] = Some(handler),
+            None => panic!("Unable to access gpio handlers")
+        }
+
+        let mut controller = Controller::new();
+        for &int in GPIO_INTERRUPTS.iter() {
+            controller.enable(int);
+        }
+    }
+
+    /// Register a handler for the local interrupt `int`, shared by all cores.
+    /// Each core enables the interrupt on its own local controller.
+    pub fn register_local(&self, int: LocalInterrupt, handler: LocalIrqHandler) {
+        self.local.lock()[LocalInterrupt::to_index(int)] = Some(handler);
+    }
+
+    /// Register `handler` as the FIQ handler and route `int` to the FIQ, for a
+    /// low latency path: the FIQ is dispatched directly, without scanning the
+    /// pending interrupts. There is a single FIQ, so this replaces any
+    /// previous handler and interrupt. `int` is no longer delivered as an IRQ.
+    ///
+    /// The handler must clear the interrupt at its source (e.g. with
+    /// `gpio::clear_events()`), or it is invoked again right away.
+    pub fn register_fiq(&self, int: Interrupt, handler: IrqHandler) {
+        *self.fiq.lock() = Some(handler);
+        Controller::new().enable_fiq(int);
+    }
+
+    /// Executes the FIQ handler. A FIQ without a handler is logged, and no
+    /// longer routed.
+    pub fn invoke_fiq(&self, tf: &mut TrapFrame) {
+        match &mut *self.fiq.lock() {
+            Some(handler) => handler(tf),
+            None => {
+                kprintln!("spurious FIQ: disabling it");
+                Controller::new().disable_fiq();
+            }
+        }
+    }
+
+    /// Executes the handler for the local interrupt `int` on the current
+    /// core.
+    pub fn invoke_local(&self, int: LocalInterrupt, tf: &mut TrapFrame) {
+        // The handler may switch processes and idle: release the lock first.
+        let handler = self.local.lock()[LocalInterrupt::to_index(int)];
+        match handler {
+            Some(handler) => handler(tf),
+            None => panic!("No handler for local interrupt {:?}", int)
+        }
+    }
+
     /// Executes an irq handler for the givven interrupt.
     /// The caller should assure that `initialize()` has been called before calling this function.
     pub fn invoke(&self, int: Interrupt, tf: &mut TrapFrame) {
         let index = Interrupt::to_index(int);
-        match &mut *self.0.lock() {
+        match &mut *self.handlers.lock() {
             Some(irq_handlers) => {
                 match &mut irq_handlers[index] {
                     Some(handler) => handler(tf),
@@ -42,4 +133,24 @@ impl Irq {
             None => panic!("Failed to open list of irq handlers")
         }
     }
+
+    /// Clears the pending GPIO events and executes the handlers of the pins
+    /// they were detected on. Events on pins without a handler are dropped.
+    fn invoke_gpio(&self, tf: &mut TrapFrame) {
+        let events = gpio::events();
+        gpio::clear_events(events);
+
+        match &mut *self.gpio.lock() {
+            Some(gpio_handlers) => {
+                for (pin, handler) in gpio_handlers.iter_mut().enumerate() {
+                    if events & (1 << pin) != 0 {
+                        if let Some(handler) = handler {
+                            handler(tf);
+                        }
+                    }
+                }
+            },
+            None => panic!("Failed to open list of gpio handlers")
+        }
+    }
 }
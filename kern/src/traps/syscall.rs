@@ -2,8 +2,11 @@ use alloc::boxed::Box;
 use core::time::Duration;
 
 use crate::console::CONSOLE;
-use crate::process::State;
+use aarch64::affinity;
+
+use crate::process::{thread, State, ALL_CORES};
 use crate::traps::TrapFrame;
+use crate::vm::VirtualAddr;
 use crate::SCHEDULER;
 use kernel_api::*;
 
@@ -27,6 +30,26 @@ pub fn sys_sleep(ms: u32, tf: &mut TrapFrame) {
     })), tf);
 }
 
+/// Blocks the calling kernel thread until `ready` returns `true` (see
+/// `thread::wait_until()`).
+///
+/// This system call takes one parameter: the address of `ready`, a
+/// `fn() -> bool`. User processes may not make it.
+///
+/// It only returns the usual status value.
+pub fn sys_wait(ready: u64, tf: &mut TrapFrame) {
+    if !thread::is_kernel_thread(tf) {
+        tf.x[7] = OsError::Unknown as u64;
+        return;
+    }
+
+    let ready: fn() -> bool = unsafe { core::mem::transmute(ready as usize) };
+    SCHEDULER.switch(State::Waiting(Box::new(move |p| {
+        p.context.x[7] = 1;
+        ready()
+    })), tf);
+}
+
 /// Returns current time.
 ///
 /// This system call does not take parameter.
@@ -44,9 +67,11 @@ pub fn sys_time(tf: &mut TrapFrame) {
 
 /// Kills current process.
 ///
-/// This system call does not take paramer and does not return any value.
+/// This system call does not take paramer and does not return any value: the
+/// next process is switched to.
 pub fn sys_exit(tf: &mut TrapFrame) {
     let _ = SCHEDULER.kill(tf);
+    SCHEDULER.switch_to(tf);
 }
 
 /// Write to console.
@@ -70,6 +95,72 @@ pub fn sys_getpid(tf: &mut TrapFrame) {
     tf.x[7] = 1;
 }
 
+/// Fills a user buffer with random bytes.
+///
+/// This system call takes two parameters: the address of the buffer and its
+/// length. The whole buffer must be mapped in the process's address space.
+///
+/// In addition to the usual status value, this system call returns one
+/// parameter: the number of bytes written.
+pub fn sys_getrandom(buf: usize, len: usize, tf: &mut TrapFrame) {
+    let mapped = SCHEDULER.critical(|scheduler| match scheduler.current(tf) {
+        Some(process) => process.vmap.is_mapped(VirtualAddr::from(buf), len),
+        None => false,
+    });
+    if !mapped {
+        tf.x[7] = OsError::BadAddress as u64;
+        return;
+    }
+
+    // The process's address space is the one installed in TTBR1.
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+    crate::rng::fill(buf);
+    tf.x[0] = len as u64;
+    tf.x[7] = 1;
+}
+
+/// Sets the affinity mask of a process.
+///
+/// This system call takes two parameters: the ID of the process and the mask
+/// of the cores it may run on. Bits of cores that do not exist are ignored;
+/// a mask without any existing core is invalid.
+///
+/// It only returns the usual status value. A process that may no longer run
+/// on the core it is queued on is moved to one it may run on: the calling
+/// process at once, another running process at its next tick.
+pub fn sys_sched_setaffinity(pid: u64, mask: u64, tf: &mut TrapFrame) {
+    let mask = mask & ALL_CORES;
+    if mask == 0 {
+        tf.x[7] = OsError::InvalidArgument as u64;
+        return;
+    }
+    if SCHEDULER.set_affinity(pid, mask).is_none() {
+        tf.x[7] = OsError::NoEntry as u64;
+        return;
+    }
+
+    tf.x[7] = 1;
+    if pid == tf.tpidr && mask & (1 << affinity()) == 0 {
+        SCHEDULER.switch(State::Ready, tf);
+    }
+}
+
+/// Returns the affinity mask of a process.
+///
+/// This system call takes one parameter: the ID of the process.
+///
+/// In addition to the usual status value, this system call returns one
+/// parameter: the mask of the cores the process may run on.
+pub fn sys_sched_getaffinity(pid: u64, tf: &mut TrapFrame) {
+    match SCHEDULER.with_process(pid, |process| process.affinity) {
+        Some(mask) => {
+            tf.x[0] = mask;
+            tf.x[7] = 1;
+        }
+        None => tf.x[7] = OsError::NoEntry as u64,
+    }
+}
+
 pub fn handle_syscall(num: u16, tf: &mut TrapFrame) {
     use crate::console::kprintln;
     match num as usize {
@@ -78,6 +169,10 @@ pub fn handle_syscall(num: u16, tf: &mut TrapFrame) {
         NR_EXIT => sys_exit(tf),
         NR_WRITE => sys_write(tf.x[0] as u8, tf),
         NR_GETPID => sys_getpid(tf),
+        NR_GETRANDOM => sys_getrandom(tf.x[0] as usize, tf.x[1] as usize, tf),
+        NR_SCHED_SETAFFINITY => sys_sched_setaffinity(tf.x[0], tf.x[1], tf),
+        NR_SCHED_GETAFFINITY => sys_sched_getaffinity(tf.x[0], tf),
+        thread::NR_WAIT => sys_wait(tf.x[0], tf),
         _ => tf.x[7] = OsError::Unknown as u64
     }
 }
@@ -1,9 +1,12 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use crate::console::kprintln;
 use crate::mutex::Mutex;
 
 use aarch64::*;
 
 mod address;
+pub mod asid;
 mod pagetable;
 
 pub use self::address::{PhysicalAddr, VirtualAddr};
@@ -11,7 +14,12 @@ pub use self::pagetable::*;
 use crate::param::{KERNEL_MASK_BITS, USER_MASK_BITS};
 
 /// Thread-safe (locking) wrapper around a kernel page table.
-pub struct VMManager(Mutex<Option<KernPageTable>>);
+pub struct VMManager {
+    kern_pt: Mutex<Option<KernPageTable>>,
+    /// The base address of `kern_pt`, readable without locking by cores that
+    /// do not have their MMU on yet.
+    kern_pt_addr: AtomicUsize,
+}
 
 impl VMManager {
     /// Returns an uninitialized `VMManager`.
@@ -19,56 +27,77 @@ impl VMManager {
     /// The virtual memory manager must be initialized by calling `initialize()` and `setup()`
     /// before the first memory allocation. Failure to do will result in panics.
     pub const fn uninitialized() -> Self {
-        VMManager(Mutex::new(None))
+        VMManager {
+            kern_pt: Mutex::new(None),
+            kern_pt_addr: AtomicUsize::new(0),
+        }
     }
 
     /// Initializes the virtual memory manager.
     /// The caller should assure that the method is invoked only once during the kernel
     /// initialization.
     pub fn initialize(&self) {
-        *self.0.lock() = Some(KernPageTable::new());
+        let kern_pt = KernPageTable::new();
+        let baddr = kern_pt.get_baddr().as_u64() as usize;
+        *self.kern_pt.lock() = Some(kern_pt);
+        self.kern_pt_addr.store(baddr, Ordering::Release);
+        // The other cores read it with their caches off.
+        unsafe { dc_civac(&self.kern_pt_addr as *const AtomicUsize as usize) };
+
+        // 16-bit ASIDs are supported if ASIDBits is 0b0010.
+        let asid16 = unsafe { ID_AA64MMFR0_EL1.get_value(ID_AA64MMFR0_EL1::ASIDBits) == 0b0010 };
+        asid::set_bits(if asid16 { 16 } else { 8 });
+
         self.setup();
     }
 
-    /// Set up the virtual memory manager.
+    /// Set up the virtual memory manager for the current core.
     /// The caller should assure that `initialize()` has been called before calling this function.
     /// Sets proper configuration bits to MAIR_EL1, TCR_EL1, TTBR0_EL1, and TTBR1_EL1 registers.
     ///
+    /// This function takes no lock, so that it can run before the MMU of the
+    /// core is on.
+    ///
     /// # Panics
     ///
     /// Panics if the current system does not support 64KB memory translation granule size.
     pub fn setup(&self) {
-        let kern_page_table = self.0.lock();
-        let baddr = kern_page_table.as_ref().unwrap().get_baddr().as_u64();
+        let baddr = self.kern_pt_addr.load(Ordering::Acquire) as u64;
+        assert!(baddr != 0, "VMManager::setup() called before initialize()");
 
         unsafe {
             assert!(ID_AA64MMFR0_EL1.get_value(ID_AA64MMFR0_EL1::TGran64) == 0);
 
             let ips = ID_AA64MMFR0_EL1.get_value(ID_AA64MMFR0_EL1::PARange);
 
+            let asid16 = ID_AA64MMFR0_EL1.get_value(ID_AA64MMFR0_EL1::ASIDBits) == 0b0010;
+
             // (ref. D7.2.70: Memory Attribute Indirection Register)
-            MAIR_EL1.set(
-                (0xFF <<  0) |// AttrIdx=0: normal, IWBWA, OWBWA, NTR
-                (0x04 <<  8) |// AttrIdx=1: device, nGnRE (must be OSH too)
-                (0x44 << 16), // AttrIdx=2: non cacheable
-            );
+            // The attribute indices are the values of `EntryAttr`. Device
+            // memory must be mapped outer shareable too.
+            MAIR_EL1.set(MAIR_EL1::Value::new(0)
+                .with_value(MairAttr::WbWa, MAIR_EL1::Attr0)
+                .with_value(MairAttr::DevNGnRE, MAIR_EL1::Attr1)
+                .with_value(MairAttr::Nc, MAIR_EL1::Attr2)
+                .get());
             // (ref. D7.2.91: Translation Control Register)
-            TCR_EL1.set(
-                (0b00 << 37) |// TBI=0, no tagging
-                (ips  << 32) |// IPS
-                (0b11 << 30) |// TG1=64k
-                (0b11 << 28) |// SH1=3 inner
-                (0b01 << 26) |// ORGN1=1 write back
-                (0b01 << 24) |// IRGN1=1 write back
-                (0b0  << 23) |// EPD1 enables higher half
-                ((USER_MASK_BITS as u64) << 16) | // T1SZ=34 (1GB)
-                (0b01 << 14) |// TG0=64k
-                (0b11 << 12) |// SH0=3 inner
-                (0b01 << 10) |// ORGN0=1 write back
-                (0b01 <<  8) |// IRGN0=1 write back
-                (0b0  <<  7) |// EPD0 enables lower half
-                ((KERNEL_MASK_BITS as u64) << 0), // T0SZ=32 (4GB)
-            );
+            // No tagging, both halves enabled, and the ASID of the user
+            // table, in TTBR1, is the one that counts.
+            TCR_EL1.set(TCR_EL1::Value::new(0)
+                .with_value(asid16 as u64, TCR_EL1::AS)
+                .with_value(ips, TCR_EL1::IPS)
+                .with_value(TcrTg1::Kb64, TCR_EL1::TG1)
+                .with_value(EntrySh::ISh, TCR_EL1::SH1)
+                .with_value(TcrRgn::WbWa, TCR_EL1::ORGN1)
+                .with_value(TcrRgn::WbWa, TCR_EL1::IRGN1)
+                .with_bit(TCR_EL1::A1)
+                .with_value(USER_MASK_BITS as u64, TCR_EL1::T1SZ) // 1GB
+                .with_value(TcrTg0::Kb64, TCR_EL1::TG0)
+                .with_value(EntrySh::ISh, TCR_EL1::SH0)
+                .with_value(TcrRgn::WbWa, TCR_EL1::ORGN0)
+                .with_value(TcrRgn::WbWa, TCR_EL1::IRGN0)
+                .with_value(KERNEL_MASK_BITS as u64, TCR_EL1::T0SZ) // 4GB
+                .get());
             isb();
 
             TTBR0_EL1.set(baddr);
@@ -83,9 +112,30 @@ impl VMManager {
         }
     }
 
+    /// Makes the kernel's `.text` writable, or read-only again, on every
+    /// core. Used to patch code, e.g. to set breakpoints. `.text` stays
+    /// executable while writable: `SCTLR_EL1.WXN` is never set.
+    pub fn set_text_writable(&self, writable: bool) {
+        let perm = if writable { EntryPerm::KERN_RW } else { EntryPerm::KERN_RO };
+        match &mut *self.kern_pt.lock() {
+            Some(pt) => pt.set_text_perm(perm),
+            None => panic!("Unable to lock VM"),
+        }
+        unsafe { tlbi_vmalle1is() };
+    }
+
+    /// Calls `f` with every mapping of the kernel page table, in increasing
+    /// address order.
+    pub fn for_each_mapping<F: FnMut(Mapping)>(&self, f: F) {
+        match &*self.kern_pt.lock() {
+            Some(pt) => pt.mappings().for_each(f),
+            None => panic!("Unable to lock VM"),
+        }
+    }
+
     /// Returns the base address of the kernel page table as `PhysicalAddr`.
     pub fn get_baddr(&self) -> PhysicalAddr {
-        match &*self.0.lock() {
+        match &*self.kern_pt.lock() {
             Some(pt) => pt.get_baddr(),
             None => panic!("Unable to lock VM"),
         }
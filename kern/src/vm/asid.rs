@@ -0,0 +1,134 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use aarch64::*;
+
+use crate::mutex::Mutex;
+use crate::param::NCORES;
+use crate::vm::PhysicalAddr;
+
+/// ASID 0 is never handed out to a process. Translations tagged with it are
+/// flushed on every context switch (see `context_restore`).
+const FIRST_ASID: u64 = 1;
+
+/// The global ASID allocator.
+static ASIDS: Mutex<AsidAllocator> = Mutex::new(AsidAllocator::new());
+
+/// The cores, one bit each, that must flush their TLB before switching to a
+/// process. Every core is marked when a new ASID generation starts.
+static FLUSH_PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// An address space identifier, tagged with the allocator generation it was
+/// handed out in. The default value is never valid.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Asid {
+    generation: u64,
+    value: u16,
+}
+
+impl Asid {
+    /// Returns the raw ASID, as written to `TTBRn_EL1.ASID`.
+    pub fn value(&self) -> u16 {
+        self.value
+    }
+}
+
+/// Hands out ASIDs in generations.
+///
+/// ASIDs are allocated in increasing order. Once they run out, a new
+/// generation starts: the TLB of every core is flushed and every process gets
+/// a fresh ASID the next time it is scheduled, since the ASIDs of the
+/// previous generation are no longer valid.
+#[derive(Debug)]
+pub struct AsidAllocator {
+    generation: u64,
+    next: u64,
+    limit: u64,
+    rollovers: u64,
+}
+
+impl AsidAllocator {
+    /// Returns an allocator handing out 8-bit ASIDs.
+    pub const fn new() -> AsidAllocator {
+        AsidAllocator {
+            generation: 1,
+            next: FIRST_ASID,
+            limit: 1 << 8,
+            rollovers: 0,
+        }
+    }
+
+    /// Sets the ASID width supported by the hardware to `bits` (8 or 16).
+    pub fn set_bits(&mut self, bits: u32) {
+        self.limit = 1 << bits;
+    }
+
+    /// Returns the number of generations that have been exhausted.
+    pub fn rollovers(&self) -> u64 {
+        self.rollovers
+    }
+
+    /// Makes sure `asid` belongs to the current generation, handing out a new
+    /// ASID if it does not. Returns `true` if a new generation was started, in
+    /// which case the TLB of every core must be flushed before it uses an
+    /// ASID of the new generation.
+    pub fn assign(&mut self, asid: &mut Asid) -> bool {
+        if asid.generation == self.generation {
+            return false;
+        }
+
+        let rollover = self.next >= self.limit;
+        if rollover {
+            self.generation += 1;
+            self.next = FIRST_ASID;
+            self.rollovers += 1;
+        }
+
+        *asid = Asid {
+            generation: self.generation,
+            value: self.next as u16,
+        };
+        self.next += 1;
+        rollover
+    }
+}
+
+/// Configures the ASID width to `bits`.
+pub fn set_bits(bits: u32) {
+    ASIDS.lock().set_bits(bits);
+}
+
+/// Returns the number of times the ASID space has been exhausted.
+pub fn rollovers() -> u64 {
+    ASIDS.lock().rollovers()
+}
+
+/// Returns the `TTBR1_EL1` value switching to the page table at `baddr` with
+/// the address space `asid`, refreshing `asid` if it is stale.
+///
+/// When a new ASID generation is started, the other cores may still run with
+/// ASIDs of the previous one and refill their TLBs with them, so a single
+/// flush does not do. Instead, every core flushes its own TLB the next time
+/// it switches to a process: the returned value is installed right away,
+/// then the TLB of the current core is flushed, so that no translation of the
+/// previous generation survives there.
+pub fn ttbr(asid: &mut Asid, baddr: PhysicalAddr) -> u64 {
+    {
+        let mut asids = ASIDS.lock();
+        if asids.assign(asid) {
+            // Under the lock, so that a core handed out an ASID of the new
+            // generation sees its flag.
+            FLUSH_PENDING.store((1 << NCORES) - 1, Ordering::SeqCst);
+        }
+    }
+    let ttbr = baddr.as_u64() | ((asid.value() as u64) << 48);
+
+    let core = 1 << affinity();
+    if FLUSH_PENDING.fetch_and(!core, Ordering::SeqCst) & core != 0 {
+        unsafe {
+            TTBR1_EL1.set(ttbr);
+            isb();
+            tlbi_vmalle1();
+        }
+    }
+    ttbr
+}
@@ -163,6 +163,65 @@ impl PageTable {
         self
     }
 
+    /// Identity maps the physical range `[start, end)` with the access
+    /// permission `perm` and the memory attribute `attr`. The range is
+    /// executable at EL1 only if `exec` is `true`; it is never executable at
+    /// EL0. Device memory is shared outer, normal memory inner.
+    fn map(&mut self, start: usize, end: usize, perm: u64, attr: u64, exec: bool) {
+        let sh = if attr == EntryAttr::Dev { EntrySh::OSh } else { EntrySh::ISh };
+        let mut curr = start;
+        while curr < end {
+            let mut entry = RawL3Entry::new(0);
+            entry.set_value(PageType::Page, RawL3Entry::TYPE);
+            entry.set_value(perm, RawL3Entry::AP);
+            entry.set_value(EntryValid::Valid, RawL3Entry::VALID);
+            entry.set_value(attr, RawL3Entry::ATTR);
+            entry.set_value(sh, RawL3Entry::SH);
+            entry.set_value(1, RawL3Entry::AF);
+            entry.set_value(!exec as u64, RawL3Entry::PXN);
+            entry.set_value(1, RawL3Entry::UXN);
+            entry.set_value((curr >> 16) as u64, RawL3Entry::ADDR);
+            self.set_entry(VirtualAddr::from(curr), entry);
+            curr += PAGE_SIZE;
+        }
+    }
+
+    /// Identity maps the 512MB block starting at `start` as device memory,
+    /// with the access permission `perm`, using a single L2 block entry. The
+    /// block is past the range covered by the L3 tables, and is never
+    /// executable.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not aligned to 512MB, or if the block is covered
+    /// by an L3 table.
+    fn map_device_block(&mut self, start: usize, perm: u64) {
+        let index = start >> 29;
+        if start % (1 << 29) != 0 || index < self.l3.len() || index >= self.l2.entries.len() {
+            panic!("invalid device block: {:#x}", start);
+        }
+
+        let entry = &mut self.l2.entries[index];
+        *entry = RawL2Entry::new(0);
+        entry.set_value(EntryType::Block, RawL2Entry::TYPE);
+        entry.set_value(perm, RawL2Entry::AP);
+        entry.set_value(EntryValid::Valid, RawL2Entry::VALID);
+        entry.set_value(EntryAttr::Dev, RawL2Entry::ATTR);
+        entry.set_value(EntrySh::OSh, RawL2Entry::SH);
+        entry.set_value(1, RawL2Entry::AF);
+        entry.set_value(1, RawL2Entry::PXN);
+        entry.set_value(1, RawL2Entry::UXN);
+        entry.set_value((start >> 16) as u64, RawL2Entry::ADDR);
+    }
+
+    /// Returns an iterator over the valid mappings of the page table. Runs of
+    /// pages with identical attributes that are contiguous both virtually and
+    /// physically are merged into a single `Mapping`. Each L2 block entry past
+    /// the L3 tables is a `Mapping` of its own, following the pages.
+    pub fn mappings(&self) -> Mappings {
+        Mappings { table: self, index: 0, block: self.l3.len() }
+    }
+
     /// Returns a base address of the pagetable. The returned `PhysicalAddr` value
     /// will point the start address of the L2PageTable.
     pub fn get_baddr(&self) -> PhysicalAddr {
@@ -179,49 +238,168 @@ impl<'a> IntoIterator for &'a PageTable {
     }
 }
 
+/// A range of contiguous pages sharing the same attributes.
+#[derive(Copy, Clone)]
+pub struct Mapping {
+    /// The virtual address of the first page, relative to the base of the
+    /// address space.
+    pub va: usize,
+    /// The physical address of the first page.
+    pub pa: usize,
+    /// The size of the range in bytes.
+    pub size: usize,
+    /// The L3 entry of the first page. For an L2 block, the block entry: its
+    /// attribute fields are laid out the same, and its type is set to that of
+    /// a page so that it reads like one.
+    pub entry: RawL3Entry,
+}
+
+impl fmt::Debug for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#011x}-{:#011x} -> {:#011x} {:?}",
+               self.va, self.va + self.size, self.pa, self.entry)
+    }
+}
+
+/// Iterator over the mappings of a `PageTable`. See `PageTable::mappings()`.
+pub struct Mappings<'a> {
+    table: &'a PageTable,
+    /// The index of the next L3 entry to visit, across both L3 tables.
+    index: usize,
+    /// The index of the next L2 entry to visit once the pages are exhausted.
+    block: usize,
+}
+
+impl<'a> Mappings<'a> {
+    /// Returns the next valid L2 block entry as a `Mapping`, if any.
+    fn next_block(&mut self) -> Option<Mapping> {
+        let entries = &self.table.l2.entries;
+        while self.block < entries.len() {
+            let (index, entry) = (self.block, entries[self.block]);
+            self.block += 1;
+            if entry.get_masked(RawL2Entry::VALID) != RawL2Entry::VALID
+                || entry.get_value(RawL2Entry::TYPE) != EntryType::Block
+            {
+                continue;
+            }
+            let mut attrs = RawL3Entry::new(entry.get());
+            attrs.set_value(PageType::Page, RawL3Entry::TYPE);
+            return Some(Mapping {
+                va: index << 29,
+                pa: entry.get_value(RawL2Entry::ADDR) as usize * PAGE_SIZE,
+                size: 1 << 29,
+                entry: attrs,
+            });
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for Mappings<'a> {
+    type Item = Mapping;
+
+    fn next(&mut self) -> Option<Mapping> {
+        let entries = self.table.into_iter().skip(self.index);
+        let mut mapping: Option<Mapping> = None;
+        for entry in entries {
+            let pa = match entry.get_page_addr() {
+                Some(pa) => pa.as_usize(),
+                None if mapping.is_some() => break,
+                None => {
+                    self.index += 1;
+                    continue;
+                }
+            };
+
+            match mapping.as_mut() {
+                None => {
+                    mapping = Some(Mapping {
+                        va: self.index * PAGE_SIZE,
+                        pa,
+                        size: PAGE_SIZE,
+                        entry: entry.0,
+                    });
+                }
+                Some(m) => {
+                    let attrs = !RawL3Entry::ADDR;
+                    if m.pa + m.size != pa || m.entry.get() & attrs != entry.0.get() & attrs {
+                        break;
+                    }
+                    m.size += PAGE_SIZE;
+                }
+            }
+            self.index += 1;
+        }
+        mapping.or_else(|| self.next_block())
+    }
+}
+
 pub struct KernPageTable(Box<PageTable>);
 
+extern "C" {
+    static __text_beg: u8;
+    static __rodata_beg: u8;
+    static __data_beg: u8;
+    static __stacks_beg: u8;
+    static __stacks_end: u8;
+}
+
+/// Returns the address of the linker symbol `sym`.
+fn symbol_addr(sym: &u8) -> usize {
+    sym as *const u8 as usize
+}
+
 impl KernPageTable {
     /// Returns a new `KernPageTable`. `KernPageTable` should have a `Pagetable`
     /// created with `KERN_RW` permission.
     ///
-    /// Set L3entry of ARM physical address starting at 0x00000000 for RAM and
-    /// physical address range from `IO_BASE` to `IO_BASE_END` for peripherals.
-    /// Each L3 entry should have correct value for lower attributes[10:0] as well
-    /// as address[47:16]. Refer to the definition of `RawL3Entry` in `vmsa.rs` for
-    /// more details.
+    /// RAM starting at 0x00000000 is identity mapped with permissions that
+    /// follow the kernel image layout given by the linker script:
+    ///
+    ///   * `.text` is read-only and executable
+    ///   * `.rodata` is read-only
+    ///   * everything else (boot stack, `.data`, `.bss`, heap) is read-write
+    ///   * but the guard page below each core's EL1 stack, which is left
+    ///     unmapped so that a stack overflow faults
+    ///
+    /// The VideoCore's share of RAM, which holds the framebuffer, is mapped
+    /// non-cacheable so that the GPU sees writes right away. The peripherals
+    /// from `IO_BASE` to `IO_BASE_END` are mapped as device memory, and so
+    /// are the local peripherals from `LOCAL_BASE`, with a block entry. Only
+    /// `.text` is executable, and never from EL0.
     pub fn new() -> KernPageTable {
         let mut pt = PageTable::new(EntryPerm::KERN_RW);
-        let start = 0x0000_0000;
-        let (_, end) = allocator::memory_map().expect("Unable to allocate memory");
-        let mut curr = start; // curr is a pointer to the memory location
-        while curr < end {
-            let mut entry = RawL3Entry::new(0);
-            entry.set_value(PageType::Page, RawL3Entry::TYPE);
-            entry.set_value(EntryPerm::KERN_RW, RawL3Entry::AP);
-            entry.set_value(EntryValid::Valid, RawL3Entry::VALID);
-            entry.set_value(EntryAttr::Mem, RawL3Entry::ATTR);
-            entry.set_value(EntrySh::ISh, RawL3Entry::SH);
-            entry.set_value(1, RawL3Entry::AF);
-            entry.set_value((curr >> 16) as u64, RawL3Entry::ADDR);
-            pt.set_entry(VirtualAddr::from(curr), entry);
-            curr += PAGE_SIZE;
-        }
-        curr = IO_BASE;
-        while curr < IO_BASE_END {
-            let mut entry = RawL3Entry::new(0);
-            entry.set_value(PageType::Page, RawL3Entry::TYPE);
-            entry.set_value(EntryPerm::KERN_RW, RawL3Entry::AP);
-            entry.set_value(EntryValid::Valid, RawL3Entry::VALID);
-            entry.set_value(EntryAttr::Dev, RawL3Entry::ATTR);
-            entry.set_value(EntrySh::OSh, RawL3Entry::SH);
-            entry.set_value(1, RawL3Entry::AF);
-            entry.set_value((curr >> 16) as u64, RawL3Entry::ADDR);
-            pt.set_entry(VirtualAddr::from(curr), entry);
-            curr += PAGE_SIZE;
+        let (text, rodata, data) = unsafe {
+            (symbol_addr(&__text_beg), symbol_addr(&__rodata_beg), symbol_addr(&__data_beg))
+        };
+        let (stacks, stacks_end) = unsafe {
+            (symbol_addr(&__stacks_beg), symbol_addr(&__stacks_end))
+        };
+        assert_eq!(stacks_end - stacks, NCORES * KERN_STACK_SLOT,
+            "the .stacks section does not match KERN_STACK_SLOT");
+        let end = allocator::memory_map().expect("Unable to allocate memory").end();
+
+        pt.map(0x0000_0000, text, EntryPerm::KERN_RW, EntryAttr::Mem, false);
+        pt.map(text, rodata, EntryPerm::KERN_RO, EntryAttr::Mem, true);
+        pt.map(rodata, data, EntryPerm::KERN_RO, EntryAttr::Mem, false);
+        pt.map(data, stacks, EntryPerm::KERN_RW, EntryAttr::Mem, false);
+        for core in 0..NCORES {
+            let stack = stacks + core * KERN_STACK_SLOT + PAGE_SIZE;
+            pt.map(stack, stack + KERN_STACK_SIZE, EntryPerm::KERN_RW, EntryAttr::Mem, false);
         }
+        pt.map(stacks_end, end, EntryPerm::KERN_RW, EntryAttr::Mem, false);
+        pt.map(end, IO_BASE, EntryPerm::KERN_RW, EntryAttr::Nc, false);
+        pt.map(IO_BASE, IO_BASE_END, EntryPerm::KERN_RW, EntryAttr::Dev, false);
+        pt.map_device_block(LOCAL_BASE, EntryPerm::KERN_RW);
         KernPageTable(pt)
     }
+
+    /// Maps `.text` with the access permission `perm`, keeping it executable.
+    /// The caller must invalidate the TLBs of every core afterwards.
+    pub fn set_text_perm(&mut self, perm: u64) {
+        let (text, rodata) = unsafe { (symbol_addr(&__text_beg), symbol_addr(&__rodata_beg)) };
+        self.map(text, rodata, perm, EntryAttr::Mem, true);
+    }
 }
 
 pub enum PagePerm {
@@ -268,10 +446,30 @@ impl UserPageTable {
         entry.set_value(EntryAttr::Mem, RawL3Entry::ATTR);
         entry.set_value(EntrySh::ISh, RawL3Entry::SH);
         entry.set_value(1, RawL3Entry::AF);
+        entry.set_value(1, RawL3Entry::NG);
         entry.set_value((frame as u64) >> 16, RawL3Entry::ADDR);
         self.0.set_entry(va, entry);
         unsafe {core::slice::from_raw_parts_mut(frame, PAGE_SIZE)}
     }
+
+    /// Returns `true` if every byte of `[va, va + len)` lies in the user
+    /// address space and is backed by an allocated page.
+    pub fn is_mapped(&self, va: VirtualAddr, len: usize) -> bool {
+        let start = va.as_usize();
+        // The user address space ends at the top of the address space.
+        if start < USER_IMG_BASE || len > 0usize.wrapping_sub(start) {
+            return false;
+        }
+        let mut page = (start & PAGE_MASK) - USER_IMG_BASE;
+        let end = start - USER_IMG_BASE + len;
+        while page < end {
+            if self.0.is_invalid(VirtualAddr::from(page)) {
+                return false;
+            }
+            page += PAGE_SIZE;
+        }
+        true
+    }
 }
 
 impl fmt::Debug for KernPageTable {
@@ -0,0 +1,37 @@
+use core::time::Duration;
+
+use pi::pm::Watchdog;
+
+use crate::mutex::Mutex;
+
+/// The watchdog, while it is armed.
+static WATCHDOG: Mutex<Option<Watchdog>> = Mutex::new(None);
+
+/// Arms the watchdog with `timeout`: the board is reset unless `pet()` is
+/// called within it. The scheduler pets the watchdog on every tick, so a
+/// kernel stuck with interrupts masked, the shell included, resets the board.
+pub fn arm(timeout: Duration) {
+    let mut watchdog = Watchdog::new();
+    watchdog.start(timeout);
+    *WATCHDOG.lock() = Some(watchdog);
+}
+
+/// Disarms the watchdog.
+pub fn disarm() {
+    if let Some(mut watchdog) = WATCHDOG.lock().take() {
+        watchdog.stop();
+    }
+}
+
+/// Returns the time left before the watchdog resets the board, or `None` if
+/// it is not armed.
+pub fn remaining() -> Option<Duration> {
+    WATCHDOG.lock().as_ref().map(|watchdog| watchdog.remaining())
+}
+
+/// Restarts the countdown of the watchdog, if it is armed.
+pub fn pet() {
+    if let Some(watchdog) = WATCHDOG.lock().as_mut() {
+        watchdog.pet();
+    }
+}
@@ -29,6 +29,94 @@ pub fn isb() {
     unsafe { asm!("isb" :::: "volatile") };
 }
 
+/// Invalidate every stage 1 EL1&0 TLB entry of this core, including the
+/// entries tagged with an ASID.
+#[inline(always)]
+pub unsafe fn tlbi_vmalle1() {
+    asm!("dsb ishst
+          tlbi vmalle1
+          dsb ish
+          isb"
+         :
+         :
+         :
+         : "volatile");
+}
+
+/// Invalidate every stage 1 EL1&0 TLB entry of every core in the inner
+/// shareable domain, including the entries tagged with an ASID.
+#[inline(always)]
+pub unsafe fn tlbi_vmalle1is() {
+    asm!("dsb ishst
+          tlbi vmalle1is
+          dsb ish
+          isb"
+         :
+         :
+         :
+         : "volatile");
+}
+
+/// Clean and invalidate the data cache line holding `addr` to the point of
+/// coherency, so that observers with their caches off see its contents.
+#[inline(always)]
+pub unsafe fn dc_civac(addr: usize) {
+    asm!("dc civac, $0
+          dsb sy"
+         :
+         : "r"(addr)
+         :
+         : "volatile");
+}
+
+/// Clean the data cache line holding `addr` to the point of unification, so
+/// that instruction fetches see its contents once the instruction cache line
+/// is invalidated with `ic_ivau()`.
+#[inline(always)]
+pub unsafe fn dc_cvau(addr: usize) {
+    asm!("dc cvau, $0
+          dsb ish"
+         :
+         : "r"(addr)
+         :
+         : "volatile");
+}
+
+/// Invalidate the instruction cache line holding `addr` to the point of
+/// unification on every core of the inner shareable domain.
+#[inline(always)]
+pub unsafe fn ic_ivau(addr: usize) {
+    asm!("ic ivau, $0
+          dsb ish
+          isb"
+         :
+         : "r"(addr)
+         :
+         : "volatile");
+}
+
+/// Translate `addr` as an EL1 read would, storing the result in `PAR_EL1`.
+#[inline(always)]
+pub unsafe fn at_s1e1r(addr: usize) {
+    asm!("at s1e1r, $0
+          isb"
+         :
+         : "r"(addr)
+         :
+         : "volatile");
+}
+
+/// Translate `addr` as an EL1 write would, storing the result in `PAR_EL1`.
+#[inline(always)]
+pub unsafe fn at_s1e1w(addr: usize) {
+    asm!("at s1e1w, $0
+          isb"
+         :
+         : "r"(addr)
+         :
+         : "volatile");
+}
+
 /// Set Event
 #[inline(always)]
 pub fn sev() {
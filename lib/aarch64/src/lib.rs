@@ -6,6 +6,9 @@
 #[macro_use]
 pub mod macros;
 
+#[cfg(test)]
+mod tests;
+
 pub mod sp;
 pub mod asm;
 pub mod regs;
@@ -38,6 +41,7 @@ pub fn sp_sel() -> u8 {
 /// # Safety
 ///
 /// This function should only be called when EL is >= 1.
+#[inline(always)]
 pub fn affinity() -> usize {
     unsafe {
         MPIDR_EL1.get_value(MPIDR_EL1::Aff0) as usize
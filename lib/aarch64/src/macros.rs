@@ -60,6 +60,45 @@ macro_rules! defreg {
                 (val & mask) >> (mask.trailing_zeros())
             }
 
+            /// A value of the register, built field by field.
+            #[derive(Copy, Clone, Debug, PartialEq)]
+            pub struct Value(u64);
+
+            impl Value {
+                #[inline(always)]
+                pub const fn new(val: u64) -> Value {
+                    Value(val)
+                }
+
+                #[inline(always)]
+                pub const fn get(self) -> u64 {
+                    self.0
+                }
+
+                #[inline(always)]
+                pub const fn get_value(self, mask: u64) -> u64 {
+                    (self.0 & mask) >> (mask.trailing_zeros())
+                }
+
+                /// Returns the value with the field `mask` set to `val`.
+                #[inline(always)]
+                pub const fn with_value(self, val: u64, mask: u64) -> Value {
+                    Value((self.0 & !mask) | ((val << (mask.trailing_zeros())) & mask))
+                }
+
+                /// Returns the value with the bits of `mask` set.
+                #[inline(always)]
+                pub const fn with_bit(self, mask: u64) -> Value {
+                    Value(self.0 | mask)
+                }
+
+                /// Returns the value with the bits of `mask` cleared.
+                #[inline(always)]
+                pub const fn without_bit(self, mask: u64) -> Value {
+                    Value(self.0 & !mask)
+                }
+            }
+
             $( define_bitfield!($field, $bits); )*
         }
 
@@ -146,7 +146,29 @@ defreg!(ELR_EL2);
 defreg!(ELR_EL3);
 
 defreg!(CPTR_EL2);
-defreg!(CPACR_EL1);
+// (ref. D13.2.30 Architectural Feature Access Control Register)
+defreg!(CPACR_EL1, [
+    FPEN [21-20], // Traps FP/SIMD accesses: 0b01 traps EL0 only, 0b11 none
+]);
+
+// (ref. C5.2.7 Floating-point Control Register)
+defreg!(FPCR, [
+    AHP   [26-26], // Alternative half-precision control
+    DN    [25-25], // Default NaN mode control
+    FZ    [24-24], // Flush-to-zero mode control
+    RMODE [23-22], // Rounding mode control
+]);
+
+// (ref. C5.2.8 Floating-point Status Register)
+defreg!(FPSR, [
+    QC  [27-27], // Cumulative saturation
+    IDC [07-07], // Input denormal cumulative exception
+    IXC [04-04], // Inexact cumulative exception
+    UFC [03-03], // Underflow cumulative exception
+    OFC [02-02], // Overflow cumulative exception
+    DZC [01-01], // Divide by zero cumulative exception
+    IOC [00-00], // Invalid operation cumulative exception
+]);
 
 // (ref. D13.2 Exception Syndrome Register)
 defreg!(ESR_EL1, [
@@ -156,10 +178,29 @@ defreg!(ESR_EL1, [
 
     ISS_HSVC_IMM [15-00], // An immediate value for HVC/SVC
     ISS_BRK_CMMT [15-00], // Comment
+
+    // The ISS of data aborts, and of instruction aborts for IFSC
+    ISV   [24-24], // Instruction syndrome valid: SAS to AR are valid
+    SAS   [23-22], // Syndrome access size
+    SSE   [21-21], // Syndrome sign extend
+    SRT   [20-16], // Syndrome register transfer
+    SF    [15-15], // 64-bit register transfer
+    AR    [14-14], // Acquire/release semantics
+    FnV   [10-10], // FAR not valid
+    EA    [09-09], // External abort type
+    CM    [08-08], // Cache maintenance
+    S1PTW [07-07], // Stage 2 fault on a stage 1 translation table walk
+    WnR   [06-06], // Write not read
+    DFSC  [05-00], // Data fault status code
+    IFSC  [05-00], // Instruction fault status code
+
+    RES0 [63-32],
 ]);
 
 // (ref. D13.2.39 Fault Address Register)
-defreg!(FAR_EL1);
+defreg!(FAR_EL1, [
+    VA   [63-00], // The faulting virtual address
+]);
 defreg!(FAR_EL2);
 defreg!(FAR_EL3);
 
@@ -184,9 +225,22 @@ defreg!(SCTLR_EL1, [
     A    [01-01], // Alignment check enable
     M    [00-00], // MMU enable for EL1 and EL0 stage 1 address translation
 
+    RES0 [63-30|27-27|21-21|17-17|13-13|10-10|06-06],
     RES1 [29-28|23-22|20-20|11-11],
 ]);
 
+// (ref. D13.3.13 Monitor Debug System Control Register)
+defreg!(MDSCR_EL1, [
+    MDE  [15-15], // Monitor debug events: breakpoints and watchpoints
+    KDE  [13-13], // Local (kernel) debug enable: debug exceptions from EL1
+    SS   [00-00], // Software step control
+]);
+
+// (ref. D13.3.19 OS Lock Access Register)
+defreg!(OSLAR_EL1, [
+    OSLK [00-00], // Locks the OS lock, which disables debug exceptions
+]);
+
 defreg!(SP_EL0);
 defreg!(SP_EL1);
 defreg!(SP_EL2);
@@ -225,3 +279,156 @@ defreg!(CNTHCTL_EL2, [
 ]);
 
 defreg!(CNTVOFF_EL2);
+
+// (ref. D7.5 The Generic Timer registers)
+defreg!(CNTFRQ_EL0);
+defreg!(CNTPCT_EL0);
+defreg!(CNTVCT_EL0);
+
+// (ref. D7.5.7 Counter-timer Physical Timer Control register)
+defreg!(CNTP_CTL_EL0, [
+    ISTATUS [2-2], // The timer condition is met
+    IMASK   [1-1], // The timer interrupt is masked
+    ENABLE  [0-0], // The timer is enabled
+
+    RES0    [63-3],
+]);
+// (ref. D7.5.8 Counter-timer Physical Timer CompareValue register)
+defreg!(CNTP_CVAL_EL0, [
+    CompareValue [63-00], // The timer condition is met once CNTPCT reaches it
+]);
+// (ref. D7.5.9 Counter-timer Physical Timer TimerValue register)
+defreg!(CNTP_TVAL_EL0, [
+    TimerValue   [31-00], // Signed ticks until the timer condition is met
+
+    RES0         [63-32],
+]);
+
+// (ref. D7.5.14 Counter-timer Virtual Timer Control register)
+defreg!(CNTV_CTL_EL0, [
+    ISTATUS [2-2], // The timer condition is met
+    IMASK   [1-1], // The timer interrupt is masked
+    ENABLE  [0-0], // The timer is enabled
+
+    RES0    [63-3],
+]);
+// (ref. D7.5.15 Counter-timer Virtual Timer CompareValue register)
+defreg!(CNTV_CVAL_EL0, [
+    CompareValue [63-00], // The timer condition is met once CNTVCT reaches it
+]);
+// (ref. D7.5.16 Counter-timer Virtual Timer TimerValue register)
+defreg!(CNTV_TVAL_EL0, [
+    TimerValue   [31-00], // Signed ticks until the timer condition is met
+
+    RES0         [63-32],
+]);
+
+// (ref. D7.4.7 Performance Monitors Control Register)
+defreg!(PMCR_EL0, [
+    IMP    [31-24], // Implementer code
+    IDCODE [23-16], // Identification code
+    N      [15-11], // Number of event counters
+    LC     [06-06], // Long cycle counter enable: overflow on bit 63
+    DP     [05-05], // Disable the cycle counter where events are prohibited
+    X      [04-04], // Export of events to an external device
+    D      [03-03], // Clock divider: count every 64 cycles
+    C      [02-02], // Cycle counter reset
+    P      [01-01], // Event counters reset
+    E      [00-00], // Enable all counters
+
+    RES0   [63-32|10-07],
+]);
+
+// (ref. D7.4.6 Performance Monitors Count Enable Set register)
+defreg!(PMCNTENSET_EL0, [
+    C      [31-31], // The cycle counter
+    P      [30-00], // The event counters, one bit each
+
+    RES0   [63-32],
+]);
+
+// (ref. D7.4.5 Performance Monitors Count Enable Clear register)
+defreg!(PMCNTENCLR_EL0, [
+    C      [31-31], // The cycle counter
+    P      [30-00], // The event counters, one bit each
+
+    RES0   [63-32],
+]);
+
+// (ref. D7.4.11 Performance Monitors Interrupt Enable Set register)
+defreg!(PMINTENSET_EL1, [
+    C      [31-31], // The cycle counter
+    P      [30-00], // The event counters, one bit each
+
+    RES0   [63-32],
+]);
+
+// (ref. D7.4.10 Performance Monitors Interrupt Enable Clear register)
+defreg!(PMINTENCLR_EL1, [
+    C      [31-31], // The cycle counter
+    P      [30-00], // The event counters, one bit each
+
+    RES0   [63-32],
+]);
+
+// (ref. D7.4.12 Performance Monitors Overflow Flag Status Clear Register)
+defreg!(PMOVSCLR_EL0, [
+    C      [31-31], // The cycle counter
+    P      [30-00], // The event counters, one bit each
+
+    RES0   [63-32],
+]);
+
+// (ref. D7.4.2 Performance Monitors Cycle Count Register)
+defreg!(PMCCNTR_EL0, [
+    CCNT   [63-00], // The processor cycle count
+]);
+
+// (ref. D7.4.1 Performance Monitors Cycle Count Filter Register)
+defreg!(PMCCFILTR_EL0, [
+    P      [31-31], // Do not count at EL1
+    U      [30-30], // Do not count at EL0
+    NSK    [29-29], // Non-secure EL1 filtering
+    NSU    [28-28], // Non-secure EL0 filtering
+    NSH    [27-27], // Count at EL2
+    M      [26-26], // Secure EL3 filtering
+
+    RES0   [63-32|25-00],
+]);
+
+// (ref. D7.4.14 Performance Monitors Event Counter Selection Register)
+defreg!(PMSELR_EL0, [
+    SEL    [04-00], // The event counter accessed through PMXEV*, 31 for PMCCFILTR
+
+    RES0   [63-05],
+]);
+
+// (ref. D7.4.18 Performance Monitors Selected Event Type Register)
+defreg!(PMXEVTYPER_EL0, [
+    P        [31-31], // Do not count at EL1
+    U        [30-30], // Do not count at EL0
+    NSK      [29-29], // Non-secure EL1 filtering
+    NSU      [28-28], // Non-secure EL0 filtering
+    NSH      [27-27], // Count at EL2
+    M        [26-26], // Secure EL3 filtering
+    evtCount [09-00], // The event to count
+
+    RES0     [63-32|25-10],
+]);
+
+// (ref. D7.4.17 Performance Monitors Selected Event Count Register)
+defreg!(PMXEVCNTR_EL0, [
+    PMEVCNT  [31-00], // The count of the selected event counter
+
+    RES0     [63-32],
+]);
+
+// (ref. D7.4.16 Performance Monitors User Enable Register)
+defreg!(PMUSERENR_EL0, [
+    ER     [03-03], // EL0 reads of the event counters
+    CR     [02-02], // EL0 reads of the cycle counter
+    SW     [01-01], // EL0 writes of PMSWINC
+    EN     [00-00], // EL0 access to every register but PMINTEN*
+
+    RES0   [63-04],
+]);
@@ -0,0 +1,172 @@
+use crate::*;
+
+/// Asserts that `fields` do not overlap and cover all 64 bits.
+fn assert_partition(fields: &[u64]) {
+    let mut covered = 0u64;
+    for &field in fields {
+        assert_eq!(covered & field, 0, "field {:#x} overlaps {:#x}", field, covered);
+        covered |= field;
+    }
+    assert_eq!(covered, !0, "bits {:#x} are not covered", !covered);
+}
+
+#[test]
+fn tcr_el1_layout() {
+    use crate::TCR_EL1::*;
+    assert_eq!(T0SZ, 0x3f);
+    assert_eq!(EPD0, 1 << 7);
+    assert_eq!(IRGN0, 0b11 << 8);
+    assert_eq!(ORGN0, 0b11 << 10);
+    assert_eq!(SH0, 0b11 << 12);
+    assert_eq!(TG0, 0b11 << 14);
+    assert_eq!(T1SZ, 0x3f << 16);
+    assert_eq!(A1, 1 << 22);
+    assert_eq!(EPD1, 1 << 23);
+    assert_eq!(IRGN1, 0b11 << 24);
+    assert_eq!(ORGN1, 0b11 << 26);
+    assert_eq!(SH1, 0b11 << 28);
+    assert_eq!(TG1, 0b11 << 30);
+    assert_eq!(IPS, 0b111 << 32);
+    assert_eq!(AS, 1 << 36);
+    assert_eq!(TBI0, 1 << 37);
+    assert_eq!(TBI1, 1 << 38);
+    assert_partition(&[T0SZ, EPD0, IRGN0, ORGN0, SH0, TG0, T1SZ, A1, EPD1, IRGN1, ORGN1,
+        SH1, TG1, IPS, AS, TBI0, TBI1, RES0]);
+}
+
+#[test]
+fn mair_el1_layout() {
+    use crate::MAIR_EL1::*;
+    let attrs = [Attr0, Attr1, Attr2, Attr3, Attr4, Attr5, Attr6, Attr7];
+    for (i, &attr) in attrs.iter().enumerate() {
+        assert_eq!(attr, 0xff << (8 * i));
+    }
+    assert_partition(&attrs);
+}
+
+#[test]
+fn sctlr_el1_layout() {
+    use crate::SCTLR_EL1::*;
+    assert_eq!(M, 1 << 0);
+    assert_eq!(A, 1 << 1);
+    assert_eq!(C, 1 << 2);
+    assert_eq!(SA, 1 << 3);
+    assert_eq!(SA0, 1 << 4);
+    assert_eq!(I, 1 << 12);
+    assert_eq!(WXN, 1 << 19);
+    assert_eq!(EE, 1 << 25);
+    assert_eq!(UCI, 1 << 26);
+    assert_eq!(RES1, 0x30d0_0800);
+    assert_partition(&[UCI, EE, EOE, WXN, nTWE, nTWI, UCT, DZE, I, UMA, SED, ITD, CP15, SA0,
+        SA, C, A, M, RES0, RES1]);
+}
+
+#[test]
+fn esr_el1_layout() {
+    use crate::ESR_EL1::*;
+    assert_eq!(EC, 0x3f << 26);
+    assert_eq!(IL, 1 << 25);
+    assert_eq!(ISS, 0x1ff_ffff);
+    assert_partition(&[EC, IL, ISS, RES0]);
+
+    assert_eq!(ISV, 1 << 24);
+    assert_eq!(SAS, 0b11 << 22);
+    assert_eq!(SRT, 0x1f << 16);
+    assert_eq!(WnR, 1 << 6);
+    assert_eq!(DFSC, 0x3f);
+    assert_eq!(IFSC, DFSC);
+
+    // A 32-bit store by x3 that hit a level 3 permission fault.
+    let esr = ESR_EL1::Value::new(0x9700_004f | 0b10 << 22 | 3 << 16);
+    assert_eq!(esr.get_value(EC), 0b100101);
+    assert_eq!(esr.get_value(ISV), 1);
+    assert_eq!(esr.get_value(SAS), 0b10);
+    assert_eq!(esr.get_value(SRT), 3);
+    assert_eq!(esr.get_value(WnR), 1);
+    assert_eq!(esr.get_value(DFSC), 0b001111);
+}
+
+#[test]
+fn far_el1_layout() {
+    assert_eq!(FAR_EL1::VA, !0);
+}
+
+#[test]
+fn generic_timer_layout() {
+    assert_eq!(CNTP_CTL_EL0::ENABLE, 1 << 0);
+    assert_eq!(CNTP_CTL_EL0::IMASK, 1 << 1);
+    assert_eq!(CNTP_CTL_EL0::ISTATUS, 1 << 2);
+    assert_partition(&[CNTP_CTL_EL0::ENABLE, CNTP_CTL_EL0::IMASK, CNTP_CTL_EL0::ISTATUS,
+        CNTP_CTL_EL0::RES0]);
+    assert_eq!(CNTP_CVAL_EL0::CompareValue, !0);
+    assert_eq!(CNTP_TVAL_EL0::TimerValue, 0xffff_ffff);
+    assert_partition(&[CNTP_TVAL_EL0::TimerValue, CNTP_TVAL_EL0::RES0]);
+}
+
+#[test]
+fn pmu_layout() {
+    use crate::PMCR_EL0::*;
+    assert_eq!(E, 1 << 0);
+    assert_eq!(P, 1 << 1);
+    assert_eq!(C, 1 << 2);
+    assert_eq!(D, 1 << 3);
+    assert_eq!(LC, 1 << 6);
+    assert_eq!(N, 0x1f << 11);
+    assert_eq!(IDCODE, 0xff << 16);
+    assert_eq!(IMP, 0xff << 24);
+    assert_partition(&[IMP, IDCODE, N, LC, DP, X, D, C, P, E, RES0]);
+
+    assert_eq!(PMCNTENSET_EL0::C, 1 << 31);
+    assert_eq!(PMCNTENSET_EL0::P, 0x7fff_ffff);
+    assert_partition(&[PMCNTENSET_EL0::C, PMCNTENSET_EL0::P, PMCNTENSET_EL0::RES0]);
+    assert_eq!(PMCCNTR_EL0::CCNT, !0);
+    assert_partition(&[PMCCFILTR_EL0::P, PMCCFILTR_EL0::U, PMCCFILTR_EL0::NSK,
+        PMCCFILTR_EL0::NSU, PMCCFILTR_EL0::NSH, PMCCFILTR_EL0::M, PMCCFILTR_EL0::RES0]);
+    assert_eq!(PMXEVTYPER_EL0::evtCount, 0x3ff);
+    assert_partition(&[PMXEVTYPER_EL0::P, PMXEVTYPER_EL0::U, PMXEVTYPER_EL0::NSK,
+        PMXEVTYPER_EL0::NSU, PMXEVTYPER_EL0::NSH, PMXEVTYPER_EL0::M,
+        PMXEVTYPER_EL0::evtCount, PMXEVTYPER_EL0::RES0]);
+    assert_eq!(PMUSERENR_EL0::EN | PMUSERENR_EL0::SW | PMUSERENR_EL0::CR
+        | PMUSERENR_EL0::ER, 0b1111);
+}
+
+#[test]
+fn builder() {
+    let value = TCR_EL1::Value::new(0)
+        .with_value(0b101, TCR_EL1::IPS)
+        .with_value(0xff, TCR_EL1::TG0)
+        .with_bit(TCR_EL1::A1 | TCR_EL1::EPD0);
+    assert_eq!(value.get(), 0b101 << 32 | 0b11 << 14 | 1 << 22 | 1 << 7);
+    assert_eq!(value.get_value(TCR_EL1::IPS), 0b101);
+
+    let value = value.without_bit(TCR_EL1::EPD0).with_value(0b01, TCR_EL1::TG0);
+    assert_eq!(value.get(), 0b101 << 32 | 0b01 << 14 | 1 << 22);
+}
+
+/// The values `VMManager::setup()` programs, against their encoding by hand.
+#[test]
+fn builder_mmu_setup() {
+    let mair = MAIR_EL1::Value::new(0)
+        .with_value(MairAttr::WbWa, MAIR_EL1::Attr0)
+        .with_value(MairAttr::DevNGnRE, MAIR_EL1::Attr1)
+        .with_value(MairAttr::Nc, MAIR_EL1::Attr2);
+    assert_eq!(mair.get(), 0x44_04_ff);
+
+    let tcr = TCR_EL1::Value::new(0)
+        .with_value(1, TCR_EL1::AS)
+        .with_value(0b001, TCR_EL1::IPS)
+        .with_value(TcrTg1::Kb64, TCR_EL1::TG1)
+        .with_value(EntrySh::ISh, TCR_EL1::SH1)
+        .with_value(TcrRgn::WbWa, TCR_EL1::ORGN1)
+        .with_value(TcrRgn::WbWa, TCR_EL1::IRGN1)
+        .with_bit(TCR_EL1::A1)
+        .with_value(34, TCR_EL1::T1SZ)
+        .with_value(TcrTg0::Kb64, TCR_EL1::TG0)
+        .with_value(EntrySh::ISh, TCR_EL1::SH0)
+        .with_value(TcrRgn::WbWa, TCR_EL1::ORGN0)
+        .with_value(TcrRgn::WbWa, TCR_EL1::IRGN0)
+        .with_value(32, TCR_EL1::T0SZ);
+    assert_eq!(tcr.get(),
+        1 << 36 | 0b001 << 32 | 0b11 << 30 | 0b11 << 28 | 0b01 << 26 | 0b01 << 24 | 1 << 22
+        | 34 << 16 | 0b01 << 14 | 0b11 << 12 | 0b01 << 10 | 0b01 << 8 | 32);
+}
@@ -45,7 +45,42 @@ pub mod EntryAttr {
     pub const Nc: u64 = 0b010;
 }
 
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+pub mod MairAttr {
+    pub const DevNGnRE: u64 = 0x04; // Device, no gathering or reordering
+    pub const Nc: u64 = 0x44;       // Normal, inner and outer non-cacheable
+    pub const WbWa: u64 = 0xFF;     // Normal, inner and outer write-back, RW-allocate
+}
+
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+pub mod TcrTg0 {
+    pub const Kb4: u64 = 0b00;
+    pub const Kb64: u64 = 0b01;
+    pub const Kb16: u64 = 0b10;
+}
+
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+pub mod TcrTg1 {
+    pub const Kb16: u64 = 0b01;
+    pub const Kb4: u64 = 0b10;
+    pub const Kb64: u64 = 0b11;
+}
+
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+pub mod TcrRgn {
+    pub const Nc: u64 = 0b00;     // Non-cacheable
+    pub const WbWa: u64 = 0b01;   // Write-back, read and write allocate
+    pub const Wt: u64 = 0b10;     // Write-through, read allocate
+    pub const WbNoWa: u64 = 0b11; // Write-back, read allocate
+}
+
 defbit!(RawL2Entry, [
+    UXN   [54-54], // Block entries only
+    PXN   [53-53], // Block entries only
     ADDR  [47-16],
 
     AF    [10-10],
@@ -58,8 +93,11 @@ defbit!(RawL2Entry, [
 ]);
 
 defbit!(RawL3Entry, [
+    UXN   [54-54],
+    PXN   [53-53],
     ADDR  [47-16],
 
+    NG    [11-11],
     AF    [10-10],
     SH    [09-08],
     AP    [07-06],
@@ -82,18 +120,48 @@ defreg!(MAIR_EL1, [
 ]);
 
 // (ref. D7.2.91: Translation Control Register)
-defreg!(TCR_EL1);
+defreg!(TCR_EL1, [
+    TBI1  [38-38], // Top byte ignored for TTBR1 addresses
+    TBI0  [37-37], // Top byte ignored for TTBR0 addresses
+    AS    [36-36], // ASID size: 16 bits if set, 8 bits otherwise
+    IPS   [34-32], // Intermediate physical address size
+    TG1   [31-30], // TTBR1 granule size (see `TcrTg1`)
+    SH1   [29-28], // TTBR1 table walk shareability (see `EntrySh`)
+    ORGN1 [27-26], // TTBR1 table walk outer cacheability (see `TcrRgn`)
+    IRGN1 [25-24], // TTBR1 table walk inner cacheability (see `TcrRgn`)
+    EPD1  [23-23], // Disables TTBR1 table walks
+    A1    [22-22], // The ASID is defined by TTBR1 if set, TTBR0 otherwise
+    T1SZ  [21-16], // TTBR1 region size: 2^(64 - T1SZ) bytes
+    TG0   [15-14], // TTBR0 granule size (see `TcrTg0`)
+    SH0   [13-12], // TTBR0 table walk shareability (see `EntrySh`)
+    ORGN0 [11-10], // TTBR0 table walk outer cacheability (see `TcrRgn`)
+    IRGN0 [09-08], // TTBR0 table walk inner cacheability (see `TcrRgn`)
+    EPD0  [07-07], // Disables TTBR0 table walks
+    T0SZ  [05-00], // TTBR0 region size: 2^(64 - T0SZ) bytes
+
+    RES0  [63-39|35-35|06-06],
+]);
 
 // (ref. D7.2.99: Translation Table Base Register 0)
 defreg!(TTBR0_EL1, [
+    ASID     [63-48],
+    BADDR    [47-01],
     TTBR_CNP [00-00],
 ]);
 
 // (ref. D7.2.102: Translation Table Base Register 1)
 defreg!(TTBR1_EL1, [
+    ASID     [63-48],
+    BADDR    [47-01],
     TTBR_CNP [00-00],
 ]);
 
+// (ref. D7.2.76: Physical Address Register)
+defreg!(PAR_EL1, [
+    PA    [47-12], // The output address, if the translation succeeded
+    F     [00-00], // The translation failed
+]);
+
 // (ref. D7.2.43: AArch64 Memory Model Feature Register 0)
 defreg!(ID_AA64MMFR0_EL1, [
     TGran4    [31-28],
@@ -170,6 +238,10 @@ impl fmt::Debug for RawL3Entry {
             _ => "????-??",
         })?;
 
+        write!(f, "{}{}",
+               if self.get_value(RawL3Entry::PXN) == 1 { "" } else { "|PX" },
+               if self.get_value(RawL3Entry::UXN) == 1 { "" } else { "|UX" })?;
+
         // NS    [05-05],
 
         write!(f, "-> {:08x} ({:x})",
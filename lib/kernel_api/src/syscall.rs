@@ -92,6 +92,61 @@ pub fn getpid() -> u64 {
     pid
 }
 
+/// Fills `buf` with random bytes from the kernel's generator. Returns the
+/// number of bytes written, which is always `buf.len()`.
+pub fn getrandom(buf: &mut [u8]) -> OsResult<usize> {
+    let mut ecode: u64;
+    let mut len: u64;
+    unsafe {
+        asm!("mov x0, $2
+              mov x1, $3
+              svc $4
+              mov $0, x0
+              mov $1, x7"
+            : "=r"(len), "=r"(ecode)
+            : "r"(buf.as_mut_ptr()), "r"(buf.len()), "i"(NR_GETRANDOM)
+            : "x0", "x1", "x7"
+            : "volatile");
+    }
+
+    err_or!(ecode, len as usize)
+}
+
+/// Restricts the process `pid` to the cores in `mask`: bit `n` stands for
+/// core `n`. Bits of cores that do not exist are ignored.
+pub fn sched_setaffinity(pid: u64, mask: u64) -> OsResult<()> {
+    let mut ecode: u64;
+    unsafe {
+        asm!("mov x0, $1
+              mov x1, $2
+              svc $3
+              mov $0, x7"
+            : "=r"(ecode)
+            : "r"(pid), "r"(mask), "i"(NR_SCHED_SETAFFINITY)
+            : "x0", "x1", "x7"
+            : "volatile");
+    }
+
+    err_or!(ecode, ())
+}
+
+/// Returns the mask of the cores the process `pid` may run on.
+pub fn sched_getaffinity(pid: u64) -> OsResult<u64> {
+    let mut ecode: u64;
+    let mut mask: u64;
+    unsafe {
+        asm!("mov x0, $2
+              svc $3
+              mov $0, x0
+              mov $1, x7"
+            : "=r"(mask), "=r"(ecode)
+            : "r"(pid), "i"(NR_SCHED_GETAFFINITY)
+            : "x0", "x7"
+            : "volatile");
+    }
+
+    err_or!(ecode, mask)
+}
 
 struct Console;
 
@@ -13,9 +13,16 @@ pub struct Atags {
 
 impl Atags {
     /// Returns an instance of `Atags`, an iterator over ATAGS on this system.
+    ///
+    /// A valid list always starts with a `CORE` ATAG. If there is none (the
+    /// firmware passes a device tree instead), the iterator is empty.
     pub fn get() -> Atags {
+        let first = unsafe { &*(ATAG_BASE as *const raw::Atag) };
         Atags {
-            ptr: Some(unsafe { &*(ATAG_BASE as *const raw::Atag) }),
+            ptr: match first.tag {
+                raw::Atag::CORE => Some(first),
+                _ => None,
+            },
         }
     }
 }
@@ -2,9 +2,18 @@
 pub const IO_BASE: usize = 0x3F000000;
 pub const IO_BASE_END: usize = 0x40000000;
 
+/// The address where the ARM local peripherals, such as the local (QA7)
+/// interrupt controller, are mapped to.
+pub const LOCAL_BASE: usize = 0x40000000;
+pub const LOCAL_BASE_END: usize = 0x40040000;
+
 /// The base address of the `GPIO` registers
 pub const GPIO_BASE: usize = IO_BASE + 0x200000;
 
+/// The frequency of the core (VPU) clock, which drives the mini UART and the
+/// I2C and SPI controllers.
+pub const CORE_CLOCK: u32 = 250_000_000;
+
 /// The number of cores in Rpi3
 pub const NCORES: usize = 4;
 
@@ -0,0 +1,342 @@
+use core::str;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The address of the device tree blob handed over by the firmware, if any.
+static FDT_BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// The magic number at the start of every device tree blob.
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// The deepest node nesting tracked while walking the structure block.
+const MAX_DEPTH: usize = 8;
+
+/// Records the address of the device tree blob the firmware passed in `x0`.
+///
+/// # Safety
+///
+/// `addr` must be zero or the address of a device tree blob that stays
+/// mapped and unmodified for the lifetime of the kernel.
+pub unsafe fn set_base(addr: usize) {
+    FDT_BASE.store(addr, Ordering::Relaxed);
+}
+
+/// A flattened device tree blob (ref: Devicetree Specification v0.2, ch. 5).
+pub struct DeviceTree<'a> {
+    data: &'a [u8],
+}
+
+impl DeviceTree<'static> {
+    /// Returns the device tree the firmware passed to the kernel, if it passed
+    /// one. See `set_base()`.
+    pub fn get() -> Option<DeviceTree<'static>> {
+        let base = FDT_BASE.load(Ordering::Relaxed);
+        if base == 0 {
+            return None;
+        }
+
+        unsafe {
+            let header = core::slice::from_raw_parts(base as *const u8, 8);
+            if be32(header, 0)? != FDT_MAGIC {
+                return None;
+            }
+            let size = be32(header, 4)? as usize;
+            DeviceTree::from_bytes(core::slice::from_raw_parts(base as *const u8, size))
+        }
+    }
+}
+
+impl<'a> DeviceTree<'a> {
+    /// Returns a device tree over the blob in `data`, or `None` if `data` does
+    /// not start with a valid header.
+    pub fn from_bytes(data: &'a [u8]) -> Option<DeviceTree<'a>> {
+        if be32(data, 0)? != FDT_MAGIC || (be32(data, 4)? as usize) > data.len() {
+            return None;
+        }
+        Some(DeviceTree { data })
+    }
+
+    /// Returns the address of the blob.
+    pub fn base(&self) -> usize {
+        self.data.as_ptr() as usize
+    }
+
+    /// Returns the size of the blob in bytes.
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Calls `f` with the `(address, size)` of every range described by the
+    /// `reg` property of the `/memory` nodes.
+    pub fn memory_regions<F: FnMut(u64, u64)>(&self, mut f: F) {
+        self.walk(|path, depth, prop, value, cells| {
+            if depth == 1 && node_name(path[1]) == "memory" && prop == "reg" {
+                for_each_reg(value, cells, &mut f);
+            }
+        });
+    }
+
+    /// Calls `f` with the `(address, size)` of every range the firmware asks
+    /// to be left alone: the memory reservation block and the `reg` property
+    /// of every child of `/reserved-memory`.
+    pub fn reserved_regions<F: FnMut(u64, u64)>(&self, mut f: F) {
+        let mut offset = self.header(16) as usize;
+        while let (Some(addr), Some(size)) = (be64(self.data, offset), be64(self.data, offset + 8)) {
+            if addr == 0 && size == 0 {
+                break;
+            }
+            f(addr, size);
+            offset += 16;
+        }
+
+        self.walk(|path, depth, prop, value, cells| {
+            if depth == 2 && path[1] == "reserved-memory" && prop == "reg" {
+                for_each_reg(value, cells, &mut f);
+            }
+        });
+    }
+
+    /// Reads the header field at byte `offset`.
+    fn header(&self, offset: usize) -> u32 {
+        be32(self.data, offset).unwrap_or(0)
+    }
+
+    /// Returns the NUL-terminated string at `offset` in the strings block.
+    fn string(&self, offset: usize) -> &'a str {
+        let start = self.header(12) as usize + offset;
+        c_str(self.data.get(start..).unwrap_or(&[]))
+    }
+
+    /// Walks the structure block, calling `f` with the names of the enclosing
+    /// nodes, the depth of the current node, and the name and value of each
+    /// property. The last argument holds the `#address-cells` and
+    /// `#size-cells` in effect for the current node's `reg` property.
+    fn walk<F>(&self, mut f: F)
+        where F: FnMut(&[&'a str; MAX_DEPTH], usize, &'a str, &'a [u8], (usize, usize))
+    {
+        let mut path = [""; MAX_DEPTH];
+        // `#address-cells` and `#size-cells` declared by each node, which
+        // apply to the `reg` property of its children.
+        let mut cells = [(2, 1); MAX_DEPTH];
+        let mut depth: isize = -1;
+        let mut offset = self.header(8) as usize;
+
+        loop {
+            let token = match be32(self.data, offset) {
+                Some(token) => token,
+                None => return,
+            };
+            offset += 4;
+
+            match token {
+                FDT_BEGIN_NODE => {
+                    depth += 1;
+                    let name = c_str(self.data.get(offset..).unwrap_or(&[]));
+                    if (depth as usize) < MAX_DEPTH {
+                        path[depth as usize] = name;
+                        cells[depth as usize] = (2, 1);
+                    }
+                    offset = align4(offset + name.len() + 1);
+                }
+                FDT_END_NODE => {
+                    depth -= 1;
+                    if depth < -1 {
+                        return;
+                    }
+                }
+                FDT_PROP => {
+                    let (len, nameoff) = match (be32(self.data, offset), be32(self.data, offset + 4)) {
+                        (Some(len), Some(nameoff)) => (len as usize, nameoff as usize),
+                        _ => return,
+                    };
+                    let value = match self.data.get(offset + 8..offset + 8 + len) {
+                        Some(value) => value,
+                        None => return,
+                    };
+                    offset = align4(offset + 8 + len);
+
+                    if depth < 0 || depth as usize >= MAX_DEPTH {
+                        continue;
+                    }
+                    let (depth, name) = (depth as usize, self.string(nameoff));
+                    match name {
+                        "#address-cells" => cells[depth].0 = be32(value, 0).unwrap_or(2) as usize,
+                        "#size-cells" => cells[depth].1 = be32(value, 0).unwrap_or(1) as usize,
+                        _ => {}
+                    }
+                    let parent = if depth == 0 { (2, 1) } else { cells[depth - 1] };
+                    f(&path, depth, name, value, parent);
+                }
+                FDT_NOP => {}
+                FDT_END => return,
+                // Unknown token: the blob is malformed.
+                _ => return,
+            }
+        }
+    }
+}
+
+/// Calls `f` with each `(address, size)` pair encoded in a `reg` property.
+fn for_each_reg<F: FnMut(u64, u64)>(value: &[u8], (addr_cells, size_cells): (usize, usize), f: &mut F) {
+    let stride = (addr_cells + size_cells) * 4;
+    if stride == 0 {
+        return;
+    }
+
+    for entry in value.chunks(stride).filter(|e| e.len() == stride) {
+        let addr = cells(&entry[..addr_cells * 4]);
+        let size = cells(&entry[addr_cells * 4..]);
+        f(addr, size);
+    }
+}
+
+/// Reads a big-endian number made of one or two 32-bit cells.
+fn cells(bytes: &[u8]) -> u64 {
+    bytes.chunks(4).fold(0, |acc, cell| (acc << 32) | be32(cell, 0).unwrap_or(0) as u64)
+}
+
+/// Returns the node name without its unit address, e.g. `memory` for
+/// `memory@0`.
+fn node_name(name: &str) -> &str {
+    name.split('@').next().unwrap_or(name)
+}
+
+fn c_str(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    str::from_utf8(&bytes[..len]).unwrap_or("")
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn be64(data: &[u8], offset: usize) -> Option<u64> {
+    Some((be32(data, offset)? as u64) << 32 | be32(data, offset + 4)? as u64)
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::DeviceTree;
+
+    /// A blob equivalent to:
+    ///
+    /// ```text
+    /// /memreserve/ 0x0 0x1000;
+    /// / {
+    ///     #address-cells = <1>;
+    ///     #size-cells = <1>;
+    ///     memory@0 { reg = <0x0 0x3b400000>; };
+    ///     reserved-memory {
+    ///         #address-cells = <1>;
+    ///         #size-cells = <1>;
+    ///         fw@3b000000 { reg = <0x3b000000 0x100000>; };
+    ///     };
+    /// };
+    /// ```
+    fn blob() -> Vec<u8> {
+        fn word(v: &mut Vec<u8>, w: u32) {
+            v.extend_from_slice(&w.to_be_bytes());
+        }
+        fn name(v: &mut Vec<u8>, s: &str) {
+            v.extend_from_slice(s.as_bytes());
+            v.push(0);
+            while v.len() % 4 != 0 {
+                v.push(0);
+            }
+        }
+        fn prop(v: &mut Vec<u8>, nameoff: u32, cells: &[u32]) {
+            word(v, 3);
+            word(v, cells.len() as u32 * 4);
+            word(v, nameoff);
+            for &c in cells {
+                word(v, c);
+            }
+        }
+
+        // "#address-cells\0#size-cells\0reg\0"
+        let strings = b"#address-cells\0#size-cells\0reg\0";
+        let (addr_cells, size_cells, reg) = (0, 15, 27);
+
+        let mut st = vec![];
+        word(&mut st, 1);
+        name(&mut st, "");
+        prop(&mut st, addr_cells, &[1]);
+        prop(&mut st, size_cells, &[1]);
+        word(&mut st, 1);
+        name(&mut st, "memory@0");
+        prop(&mut st, reg, &[0x0, 0x3b40_0000]);
+        word(&mut st, 2);
+        word(&mut st, 1);
+        name(&mut st, "reserved-memory");
+        prop(&mut st, addr_cells, &[1]);
+        prop(&mut st, size_cells, &[1]);
+        word(&mut st, 1);
+        name(&mut st, "fw@3b000000");
+        prop(&mut st, reg, &[0x3b00_0000, 0x10_0000]);
+        word(&mut st, 2);
+        word(&mut st, 2);
+        word(&mut st, 2);
+        word(&mut st, 9);
+
+        let rsvmap: [u64; 4] = [0x0, 0x1000, 0, 0];
+        let off_rsvmap = 40;
+        let off_struct = off_rsvmap + 32;
+        let off_strings = off_struct + st.len();
+        let total = off_strings + strings.len();
+
+        let mut v = vec![];
+        for &w in &[0xd00d_feed, total as u32, off_struct as u32, off_strings as u32,
+                    off_rsvmap as u32, 17, 16, 0, strings.len() as u32, st.len() as u32] {
+            word(&mut v, w);
+        }
+        for &r in &rsvmap {
+            v.extend_from_slice(&r.to_be_bytes());
+        }
+        v.extend_from_slice(&st);
+        v.extend_from_slice(strings);
+        v
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut data = blob();
+        data[0] = 0;
+        assert!(DeviceTree::from_bytes(&data).is_none());
+        assert!(DeviceTree::from_bytes(&[]).is_none());
+    }
+
+    #[test]
+    fn test_memory() {
+        let data = blob();
+        let fdt = DeviceTree::from_bytes(&data).expect("valid blob");
+
+        let mut regions = vec![];
+        fdt.memory_regions(|addr, size| regions.push((addr, size)));
+        assert_eq!(regions, vec![(0x0, 0x3b40_0000)]);
+    }
+
+    #[test]
+    fn test_reserved() {
+        let data = blob();
+        let fdt = DeviceTree::from_bytes(&data).expect("valid blob");
+
+        let mut regions = vec![];
+        fdt.reserved_regions(|addr, size| regions.push((addr, size)));
+        assert_eq!(regions, vec![(0x0, 0x1000), (0x3b00_0000, 0x10_0000)]);
+    }
+}
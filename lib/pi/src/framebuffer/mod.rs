@@ -0,0 +1,154 @@
+pub mod font;
+
+use core::ptr;
+use core::slice;
+
+use crate::mailbox::{self, PropertyBuffer, Tag};
+
+/// The number of bits per pixel requested from the firmware.
+const DEPTH: u32 = 32;
+
+/// The alignment requested for the framebuffer.
+const ALIGN: u32 = 4096;
+
+/// Mask turning a VideoCore bus address into an ARM physical address.
+const BUS_ADDR_MASK: u32 = 0x3FFF_FFFF;
+
+/// A 24-bit color.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(0xff, 0xff, 0xff);
+
+    /// Returns the color with the given red, green and blue components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b }
+    }
+}
+
+/// The order of the color components of a pixel in memory.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum PixelOrder {
+    Bgr,
+    Rgb,
+}
+
+/// A 32-bit linear framebuffer allocated by the VideoCore firmware.
+pub struct Framebuffer {
+    pixels: &'static mut [u32],
+    width: usize,
+    height: usize,
+    /// The distance between two rows, in pixels.
+    stride: usize,
+    order: PixelOrder,
+}
+
+impl Framebuffer {
+    /// Asks the firmware for a `width` x `height` framebuffer and returns it.
+    ///
+    /// The framebuffer lives in the VideoCore's share of memory, which must be
+    /// mapped for the returned framebuffer to be usable.
+    pub fn new(width: usize, height: usize) -> Result<Framebuffer, mailbox::Error> {
+        let (w, h) = (width as u32, height as u32);
+        let mut buf = PropertyBuffer::new();
+        let size = buf.push(Tag::SetPhysicalSize, &[w, h]);
+        buf.push(Tag::SetVirtualSize, &[w, h]);
+        buf.push(Tag::SetVirtualOffset, &[0, 0]);
+        let depth = buf.push(Tag::SetDepth, &[DEPTH]);
+        let order = buf.push(Tag::SetPixelOrder, &[1]);
+        let alloc = buf.push(Tag::AllocateBuffer, &[ALIGN, 0]);
+        let pitch = buf.push(Tag::GetPitch, &[0]);
+        buf.send()?;
+
+        let (width, height) = {
+            let size = buf.response(size)?;
+            (size[0] as usize, size[1] as usize)
+        };
+        let (base, len) = {
+            let alloc = buf.response(alloc)?;
+            ((alloc[0] & BUS_ADDR_MASK) as usize, alloc[1] as usize)
+        };
+        if buf.response(depth)?[0] != DEPTH || base == 0 {
+            return Err(mailbox::Error::Unanswered);
+        }
+        let order = match buf.response(order)?[0] {
+            0 => PixelOrder::Bgr,
+            _ => PixelOrder::Rgb,
+        };
+        let stride = buf.response(pitch)?[0] as usize / 4;
+
+        let pixels = unsafe { slice::from_raw_parts_mut(base as *mut u32, len / 4) };
+        Ok(Framebuffer { pixels, width, height, stride, order })
+    }
+
+    /// Returns the width of the framebuffer in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the framebuffer in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the pixel value of `color`.
+    fn encode(&self, color: Color) -> u32 {
+        let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+        match self.order {
+            PixelOrder::Rgb => r | g << 8 | b << 16,
+            PixelOrder::Bgr => b | g << 8 | r << 16,
+        }
+    }
+
+    /// Sets the pixel at (`x`, `y`) to `color`. Pixels outside of the
+    /// framebuffer are ignored.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            let value = self.encode(color);
+            unsafe { ptr::write_volatile(&mut self.pixels[y * self.stride + x], value) };
+        }
+    }
+
+    /// Fills the `w` x `h` rectangle whose top left corner is at (`x`, `y`)
+    /// with `color`, clipped to the framebuffer.
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: Color) {
+        let value = self.encode(color);
+        let (x_end, y_end) = ((x + w).min(self.width), (y + h).min(self.height));
+        for row in y.min(y_end)..y_end {
+            let start = row * self.stride;
+            for pixel in &mut self.pixels[start + x.min(x_end)..start + x_end] {
+                unsafe { ptr::write_volatile(pixel, value) };
+            }
+        }
+    }
+
+    /// Draws the glyph of the ASCII character `c` with its top left corner at
+    /// (`x`, `y`). Set bits are drawn with `fg`, the others with `bg`.
+    pub fn draw_char(&mut self, x: usize, y: usize, c: u8, fg: Color, bg: Color) {
+        let glyph = font::glyph(c);
+        for (dy, &bits) in glyph.iter().enumerate() {
+            for dx in 0..font::WIDTH {
+                let color = if bits & (0x80 >> dx) != 0 { fg } else { bg };
+                self.set_pixel(x + dx, y + dy, color);
+            }
+        }
+    }
+
+    /// Moves the contents of the framebuffer up by `rows` pixel rows and
+    /// fills the rows uncovered at the bottom with `bg`.
+    pub fn scroll_up(&mut self, rows: usize, bg: Color) {
+        let rows = rows.min(self.height);
+        let (stride, height) = (self.stride, self.height);
+        unsafe {
+            let base = self.pixels.as_mut_ptr();
+            ptr::copy(base.add(rows * stride), base, (height - rows) * stride);
+        }
+        self.fill_rect(0, height - rows, self.width, rows, bg);
+    }
+}
@@ -1,6 +1,8 @@
 use core::marker::PhantomData;
+use core::time::Duration;
 
 use crate::common::{states, GPIO_BASE};
+use crate::timer;
 use volatile::prelude::*;
 use volatile::{ReadVolatile, Reserved, Volatile, WriteVolatile};
 
@@ -17,6 +19,36 @@ pub enum Function {
     Alt5 = 0b010,
 }
 
+/// An event that can be detected on an input pin. Detected events are
+/// latched in `GPEDS` and raise the GPIO interrupts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Event {
+    /// A low to high transition, sampled with the system clock.
+    RisingEdge,
+    /// A high to low transition, sampled with the system clock.
+    FallingEdge,
+    /// The pin being high. Re-asserted as long as the level holds.
+    HighLevel,
+    /// The pin being low. Re-asserted as long as the level holds.
+    LowLevel,
+    /// A low to high transition, not sampled: detects very short pulses.
+    AsyncRisingEdge,
+    /// A high to low transition, not sampled: detects very short pulses.
+    AsyncFallingEdge,
+}
+
+/// The pull-up/down resistor configuration of a pin.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pull {
+    Off = 0b00,
+    Down = 0b01,
+    Up = 0b10,
+}
+
+/// The number of GPIO pins.
+pub const NUM_PINS: usize = 54;
+
 #[repr(C)]
 #[allow(non_snake_case)]
 struct Registers {
@@ -144,4 +176,82 @@ impl Gpio<Input> {
         let shift = (self.pin as usize)-(index * 32);
         self.registers.LEV[index].has_mask(1 << shift)
     }
+
+    /// Returns the pin number.
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Returns the detect enable register for `event` and the bit of this
+    /// pin in it.
+    fn event_register(&mut self, event: Event) -> (&mut Volatile<u32>, u32) {
+        let index = (self.pin / 32) as usize;
+        let bit = 1 << (self.pin as usize - index * 32);
+        let register = match event {
+            Event::RisingEdge => &mut self.registers.REN[index],
+            Event::FallingEdge => &mut self.registers.FEN[index],
+            Event::HighLevel => &mut self.registers.HEN[index],
+            Event::LowLevel => &mut self.registers.LEN[index],
+            Event::AsyncRisingEdge => &mut self.registers.AREN[index],
+            Event::AsyncFallingEdge => &mut self.registers.AFEN[index],
+        };
+        (register, bit)
+    }
+
+    /// Starts detecting `event` on this pin. Several events can be enabled at
+    /// once.
+    pub fn enable_event(&mut self, event: Event) {
+        let (register, bit) = self.event_register(event);
+        register.or_mask(bit);
+    }
+
+    /// Stops detecting `event` on this pin.
+    pub fn disable_event(&mut self, event: Event) {
+        let (register, bit) = self.event_register(event);
+        register.and_mask(!bit);
+    }
+
+    /// Returns `true` if one of the enabled events has been detected on this
+    /// pin since the last call to `clear_event()`.
+    pub fn has_event(&mut self) -> bool {
+        events() & (1 << self.pin) != 0
+    }
+
+    /// Clears the event detected on this pin, if any.
+    pub fn clear_event(&mut self) {
+        clear_events(1 << self.pin);
+    }
+
+    /// Configures the pull-up/down resistor of this pin. The setting is kept
+    /// across resets, but not across power cycles.
+    pub fn set_pull(&mut self, pull: Pull) {
+        // The control signal must be set up, then clocked into the pin, each
+        // for at least 150 cycles (ref: BCM2837 page 101).
+        let setup = Duration::from_micros(5);
+        let index = (self.pin / 32) as usize;
+        let bit = 1 << (self.pin as usize - index * 32);
+
+        self.registers.PUD.write(pull as u32);
+        timer::spin_sleep(setup);
+        self.registers.PUDCLK[index].write(bit);
+        timer::spin_sleep(setup);
+        self.registers.PUD.write(0);
+        self.registers.PUDCLK[index].write(0);
+    }
+}
+
+/// Returns the pins on which an event has been detected (`GPEDS0` and
+/// `GPEDS1`): bit `n` is set if an event is pending on pin `n`.
+pub fn events() -> u64 {
+    let registers = unsafe { &*(GPIO_BASE as *const Registers) };
+    (registers.EDS[1].read() as u64) << 32 | registers.EDS[0].read() as u64
+}
+
+/// Clears the events detected on the pins set in `pins`, with the same
+/// layout as `events()`.
+pub fn clear_events(pins: u64) {
+    let registers = unsafe { &mut *(GPIO_BASE as *mut Registers) };
+    // Event bits are cleared by writing a 1.
+    registers.EDS[0].write(pins as u32);
+    registers.EDS[1].write((pins >> 32) as u32);
 }
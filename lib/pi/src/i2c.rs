@@ -0,0 +1,247 @@
+use core::time::Duration;
+
+use shim::const_assert_size;
+use shim::io;
+
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use crate::common::{CORE_CLOCK, IO_BASE};
+use crate::gpio::{Function, Gpio};
+use crate::timer;
+
+/// The base address for the `BSC1` registers.
+const BSC1_REG_BASE: usize = IO_BASE + 0x804000;
+
+/// The default (standard mode) SCL frequency.
+pub const DEFAULT_SPEED: u32 = 100_000;
+
+/// The default time a transfer may take before it is aborted.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The size of the controller's FIFO, in bytes.
+const FIFO_SIZE: usize = 16;
+
+/// Enum representing bit fields of the `C` register.
+#[repr(u32)]
+enum Control {
+    Read = 1 << 0,
+    ClearFifo = 0b01 << 4,
+    Start = 1 << 7,
+    Enable = 1 << 15,
+}
+
+/// Enum representing bit fields of the `S` register.
+#[repr(u32)]
+enum Status {
+    Active = 1 << 0,
+    Done = 1 << 1,
+    TxData = 1 << 4,
+    RxData = 1 << 5,
+    Nack = 1 << 8,
+    ClockTimeout = 1 << 9,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // Declare the BSC registers from page 28.
+    C: Volatile<u32>,
+    S: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    A: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    DIV: Volatile<u32>,
+    DEL: Volatile<u32>,
+    CLKT: Volatile<u32>,
+}
+
+const_assert_size!(Registers, 0x7E804020 - 0x7E804000);
+
+/// An error ending an I2C transfer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Error {
+    /// The slave did not acknowledge its address or a byte.
+    Nack,
+    /// The slave stretched the clock for longer than allowed by `CLKT`.
+    ClockStretch,
+    /// The transfer did not complete within the timeout.
+    Timeout,
+    /// The address does not fit in 7 bits.
+    InvalidAddress,
+    /// The transfer is too long for the controller.
+    TooLong,
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        match error {
+            Error::Nack => io::Error::new(io::ErrorKind::ConnectionRefused, "no acknowledgement"),
+            Error::ClockStretch => io::Error::new(io::ErrorKind::TimedOut, "clock stretch timeout"),
+            Error::Timeout => io::Error::new(io::ErrorKind::TimedOut, "timeout"),
+            Error::InvalidAddress => io::Error::new(io::ErrorKind::InvalidInput, "invalid address"),
+            Error::TooLong => io::Error::new(io::ErrorKind::InvalidInput, "transfer too long"),
+        }
+    }
+}
+
+/// The Raspberry Pi's `BSC1` I2C master, on GPIO pins 2 (SDA) and 3 (SCL).
+pub struct I2c {
+    registers: &'static mut Registers,
+    timeout: Duration,
+}
+
+impl I2c {
+    /// Initializes the `BSC1` controller at `DEFAULT_SPEED`, setting GPIO
+    /// pins 2 and 3 to alternative function 0 (SDA1/SCL1), and enables it.
+    ///
+    /// Transfers time out after `DEFAULT_TIMEOUT`. To change it, use
+    /// `set_timeout()`.
+    pub fn new() -> I2c {
+        let registers = unsafe { &mut *(BSC1_REG_BASE as *mut Registers) };
+
+        Gpio::new(2).into_alt(Function::Alt0);
+        Gpio::new(3).into_alt(Function::Alt0);
+
+        registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        registers.S.write(Status::Done as u32 | Status::Nack as u32
+            | Status::ClockTimeout as u32);
+
+        let mut i2c = I2c { registers, timeout: DEFAULT_TIMEOUT };
+        i2c.set_speed(DEFAULT_SPEED);
+        i2c
+    }
+
+    /// Sets the clock divider: SCL runs at `CORE_CLOCK / div`. The divider is
+    /// rounded down to an even number by the controller.
+    pub fn set_clock_divider(&mut self, div: u16) {
+        self.registers.DIV.write(div as u32);
+    }
+
+    /// Sets the divider so that SCL runs at most at `hz`.
+    pub fn set_speed(&mut self, hz: u32) {
+        let div = (CORE_CLOCK + hz - 1) / hz;
+        // Round up to an even divider, as the controller rounds down.
+        let div = (div + 1) & !1;
+        self.set_clock_divider(div.max(2).min(0xfffe) as u16);
+    }
+
+    /// Sets the time a transfer may take before it is aborted.
+    pub fn set_timeout(&mut self, t: Duration) {
+        self.timeout = t;
+    }
+
+    /// Clears the FIFO and the status flags and sets up a transfer of `len`
+    /// bytes with the slave at `addr`. The transfer is not started.
+    fn setup(&mut self, addr: u8, len: usize) -> Result<(), Error> {
+        if addr > 0x7f {
+            return Err(Error::InvalidAddress);
+        }
+        if len > 0xffff {
+            return Err(Error::TooLong);
+        }
+
+        self.registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        self.registers.S.write(Status::Done as u32 | Status::Nack as u32
+            | Status::ClockTimeout as u32);
+        self.registers.A.write(addr as u32);
+        self.registers.DLEN.write(len as u32);
+        Ok(())
+    }
+
+    /// Starts the transfer set up last, reading from the slave if `read` is
+    /// `true` and writing to it otherwise.
+    fn start(&mut self, read: bool) {
+        let read = if read { Control::Read as u32 } else { 0 };
+        self.registers.C.write(Control::Enable as u32 | Control::Start as u32 | read);
+    }
+
+    /// Stops the current transfer and clears the FIFO and the status flags.
+    fn abort(&mut self) {
+        self.registers.C.write(Control::Enable as u32 | Control::ClearFifo as u32);
+        self.registers.S.write(Status::Done as u32 | Status::Nack as u32
+            | Status::ClockTimeout as u32);
+    }
+
+    /// Waits for the current transfer to complete, feeding the FIFO from `tx`
+    /// and emptying it into `rx` as the controller asks for it.
+    fn complete(&mut self, tx: &[u8], rx: &mut [u8]) -> Result<(), Error> {
+        let beginning = timer::current_time();
+        let (mut sent, mut received) = (0, 0);
+        loop {
+            let status = self.registers.S.read();
+            if status & Status::Nack as u32 != 0 {
+                self.abort();
+                return Err(Error::Nack);
+            }
+            if status & Status::ClockTimeout as u32 != 0 {
+                self.abort();
+                return Err(Error::ClockStretch);
+            }
+
+            while sent < tx.len() && self.registers.S.has_mask(Status::TxData as u32) {
+                self.registers.FIFO.write(tx[sent] as u32);
+                sent += 1;
+            }
+            while received < rx.len() && self.registers.S.has_mask(Status::RxData as u32) {
+                rx[received] = self.registers.FIFO.read() as u8;
+                received += 1;
+            }
+
+            if status & Status::Done as u32 != 0 && received == rx.len() {
+                self.registers.S.write(Status::Done as u32);
+                return Ok(());
+            }
+            if timer::current_time() > beginning + self.timeout {
+                self.abort();
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// Writes `data` to the slave at the 7-bit address `addr`.
+    pub fn write(&mut self, addr: u8, data: &[u8]) -> io::Result<()> {
+        self.setup(addr, data.len())?;
+        self.start(false);
+        Ok(self.complete(data, &mut [])?)
+    }
+
+    /// Reads `buf.len()` bytes from the slave at the 7-bit address `addr`.
+    pub fn read(&mut self, addr: u8, buf: &mut [u8]) -> io::Result<()> {
+        self.setup(addr, buf.len())?;
+        self.start(true);
+        Ok(self.complete(&[], buf)?)
+    }
+
+    /// Writes `data` to the slave at the 7-bit address `addr`, then reads
+    /// `buf.len()` bytes from it after a repeated start, as is usual to read
+    /// a device register. `data` must fit in the FIFO (16 bytes).
+    pub fn write_read(&mut self, addr: u8, data: &[u8], buf: &mut [u8]) -> io::Result<()> {
+        if data.len() > FIFO_SIZE || buf.len() > 0xffff {
+            return Err(Error::TooLong.into());
+        }
+        if buf.is_empty() {
+            return self.write(addr, data);
+        }
+
+        // The controller has no repeated start: the read is started as soon
+        // as the write is under way, so that it follows it without a stop.
+        self.setup(addr, data.len())?;
+        for &byte in data {
+            self.registers.FIFO.write(byte as u32);
+        }
+        self.start(false);
+
+        let beginning = timer::current_time();
+        while self.registers.S.read() & (Status::Active as u32 | Status::Done as u32) == 0 {
+            if timer::current_time() > beginning + self.timeout {
+                self.abort();
+                return Err(Error::Timeout.into());
+            }
+        }
+
+        self.registers.DLEN.write(buf.len() as u32);
+        self.start(true);
+        Ok(self.complete(&[], buf)?)
+    }
+}
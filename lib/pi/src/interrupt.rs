@@ -5,24 +5,30 @@ use volatile::{Volatile, ReadVolatile};
 
 const INT_BASE: usize = IO_BASE + 0xB000 + 0x200;
 
+/// The enable bit of the FIQ control register. The source is in bits 0-6.
+const FIQ_ENABLE: u32 = 1 << 7;
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum Interrupt {
     Timer1 = 1,
     Timer3 = 3,
     Usb = 9,
+    Aux = 29,
     Gpio0 = 49,
     Gpio1 = 50,
     Gpio2 = 51,
     Gpio3 = 52,
+    Spi = 54,
+    /// The PL011 UART. The mini UART interrupts through `Aux`.
     Uart = 57,
 }
 
 impl Interrupt {
-    pub const MAX: usize = 8;
+    pub const MAX: usize = 10;
 
     pub fn iter() -> core::slice::Iter<'static, Interrupt> {
         use Interrupt::*;
-        [Timer1, Timer3, Usb, Gpio0, Gpio1, Gpio2, Gpio3, Uart].into_iter()
+        [Timer1, Timer3, Usb, Gpio0, Gpio1, Gpio2, Gpio3, Uart, Aux, Spi].into_iter()
     }
 
     pub fn to_index(i: Interrupt) -> usize {
@@ -36,6 +42,8 @@ impl Interrupt {
             Gpio2 => 5,
             Gpio3 => 6,
             Uart => 7,
+            Aux => 8,
+            Spi => 9,
         }
     }
 
@@ -50,6 +58,8 @@ impl Interrupt {
             5 => Gpio2,
             6 => Gpio3,
             7 => Uart,
+            8 => Aux,
+            9 => Spi,
             _ => panic!("Unknown interrupt: {}", i),
         }
     }
@@ -67,7 +77,9 @@ impl From<usize> for Interrupt {
             50 => Gpio1,
             51 => Gpio2,
             52 => Gpio3,
+            54 => Spi,
             57 => Uart,
+            29 => Aux,
             _ => panic!("Unkonwn irq: {}", irq),
         }
     }
@@ -79,7 +91,7 @@ struct Registers {
     // Fill me in.
     pending_basic: u32,
     pending: [ReadVolatile<u32>; 2],
-    fiq_control: u32,
+    fiq_control: Volatile<u32>,
     enable: [Volatile<u32>; 2],
     enable_basic: u32,
     disable: [Volatile<u32>; 2],
@@ -120,6 +132,19 @@ impl Controller {
         }
     }
 
+    /// Routes `int` to the FIQ instead of the IRQ. Only one interrupt can be
+    /// routed to the FIQ at a time: this replaces the previous one, which is
+    /// left disabled.
+    pub fn enable_fiq(&mut self, int: Interrupt) {
+        self.disable(int);
+        self.registers.fiq_control.write(FIQ_ENABLE | int as u32);
+    }
+
+    /// Stops routing any interrupt to the FIQ.
+    pub fn disable_fiq(&mut self) {
+        self.registers.fiq_control.write(0);
+    }
+
     /// Returns `true` if `int` is pending. Otherwise, returns `false`.
     pub fn is_pending(&self, int: Interrupt) -> bool {
         let index = int as u64;
@@ -7,7 +7,17 @@
 
 pub mod atags;
 pub mod common;
+pub mod fdt;
+pub mod framebuffer;
 pub mod gpio;
+pub mod i2c;
 pub mod interrupt;
+pub mod local_interrupt;
+pub mod mailbox;
+pub mod pl011;
+pub mod pm;
+pub mod pwm;
+pub mod rng;
+pub mod spi;
 pub mod timer;
 pub mod uart;
@@ -0,0 +1,172 @@
+use shim::const_assert_size;
+
+use volatile::prelude::*;
+use volatile::{ReadVolatile, Reserved, Volatile, WriteVolatile};
+
+use crate::common::LOCAL_BASE;
+
+/// The `GPU` bit of the core IRQ source registers: one of the interrupts of
+/// the `interrupt::Controller` is pending.
+const GPU_PENDING: u32 = 1 << 8;
+
+/// The interrupts of the BCM2836/7 local (QA7) interrupt controller. Each
+/// core has its own copy of these interrupts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LocalInterrupt {
+    /// The secure physical timer, `CNTPS`.
+    CntPs = 0,
+    /// The non-secure physical timer, `CNTP`: the one EL1 uses.
+    CntPns = 1,
+    /// The hypervisor physical timer, `CNTHP`.
+    CntHp = 2,
+    /// The virtual timer, `CNTV`.
+    CntV = 3,
+    Mailbox0 = 4,
+    Mailbox1 = 5,
+    Mailbox2 = 6,
+    Mailbox3 = 7,
+    /// The performance monitors.
+    Pmu = 9,
+    /// The local timer, shared by all cores and routed to one of them.
+    LocalTimer = 11,
+}
+
+impl LocalInterrupt {
+    pub const MAX: usize = 10;
+
+    pub fn iter() -> core::slice::Iter<'static, LocalInterrupt> {
+        use LocalInterrupt::*;
+        [CntPs, CntPns, CntHp, CntV, Mailbox0, Mailbox1, Mailbox2, Mailbox3, Pmu, LocalTimer]
+            .into_iter()
+    }
+
+    pub fn to_index(i: LocalInterrupt) -> usize {
+        use LocalInterrupt::*;
+        match i {
+            CntPs => 0,
+            CntPns => 1,
+            CntHp => 2,
+            CntV => 3,
+            Mailbox0 => 4,
+            Mailbox1 => 5,
+            Mailbox2 => 6,
+            Mailbox3 => 7,
+            Pmu => 8,
+            LocalTimer => 9,
+        }
+    }
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // Declare the registers from the QA7 manual, page 7.
+    CONTROL: Volatile<u32>,
+    __r0: Reserved<u32>,
+    PRESCALER: Volatile<u32>,
+    GPU_ROUTING: Volatile<u32>,
+    PMU_ROUTING_SET: WriteVolatile<u32>,
+    PMU_ROUTING_CLR: WriteVolatile<u32>,
+    __r1: Reserved<u32>,
+    TIMER_LS: Volatile<u32>,
+    TIMER_MS: Volatile<u32>,
+    LOCAL_ROUTING: Volatile<u32>,
+    __r2: [Reserved<u32>; 3],
+    LOCAL_TIMER_CTL: Volatile<u32>,
+    LOCAL_TIMER_FLAGS: WriteVolatile<u32>,
+    __r3: Reserved<u32>,
+    TIMER_CNTL: [Volatile<u32>; 4],
+    MAILBOX_CNTL: [Volatile<u32>; 4],
+    IRQ_SOURCE: [ReadVolatile<u32>; 4],
+    FIQ_SOURCE: [ReadVolatile<u32>; 4],
+    /// Write-set registers, by core then by mailbox.
+    MAILBOX_SET: [[WriteVolatile<u32>; 4]; 4],
+    /// Read and write-high-to-clear registers, by core then by mailbox.
+    MAILBOX_CLR: [[Volatile<u32>; 4]; 4],
+}
+
+const_assert_size!(Registers, 0x40000100 - 0x40000000);
+
+/// The local interrupt controller of a core. Used to route the core's timer
+/// and mailbox interrupts to it, and to check which of them are pending.
+pub struct LocalController {
+    core: usize,
+    registers: &'static mut Registers,
+}
+
+impl LocalController {
+    /// Returns a new handle to the local interrupt controller of `core`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `core` is not a valid core number.
+    pub fn new(core: usize) -> LocalController {
+        assert!(core < 4, "invalid core: {}", core);
+        LocalController {
+            core,
+            registers: unsafe { &mut *(LOCAL_BASE as *mut Registers) },
+        }
+    }
+
+    /// Enables the interrupt `int` as an IRQ of the core. The local timer is
+    /// routed to the core, away from any other.
+    pub fn enable(&mut self, int: LocalInterrupt) {
+        use LocalInterrupt::*;
+        let core = self.core;
+        match int {
+            CntPs | CntPns | CntHp | CntV => self.registers.TIMER_CNTL[core].or_mask(1 << int as u32),
+            Mailbox0 | Mailbox1 | Mailbox2 | Mailbox3 => {
+                self.registers.MAILBOX_CNTL[core].or_mask(1 << (int as u32 - Mailbox0 as u32))
+            }
+            Pmu => self.registers.PMU_ROUTING_SET.write(1 << core),
+            LocalTimer => self.registers.LOCAL_ROUTING.write(core as u32),
+        }
+    }
+
+    /// Disables the interrupt `int` for the core. The local timer cannot be
+    /// disabled this way: it is always routed to some core.
+    pub fn disable(&mut self, int: LocalInterrupt) {
+        use LocalInterrupt::*;
+        let core = self.core;
+        match int {
+            CntPs | CntPns | CntHp | CntV => {
+                self.registers.TIMER_CNTL[core].and_mask(!(1 << int as u32))
+            }
+            Mailbox0 | Mailbox1 | Mailbox2 | Mailbox3 => {
+                self.registers.MAILBOX_CNTL[core].and_mask(!(1 << (int as u32 - Mailbox0 as u32)))
+            }
+            Pmu => self.registers.PMU_ROUTING_CLR.write(1 << core),
+            LocalTimer => (),
+        }
+    }
+
+    /// Returns `true` if `int` is pending as an IRQ of the core. Otherwise,
+    /// returns `false`.
+    pub fn is_pending(&self, int: LocalInterrupt) -> bool {
+        self.registers.IRQ_SOURCE[self.core].has_mask(1 << int as u32)
+    }
+
+    /// Returns `true` if one of the interrupts of the `interrupt::Controller`
+    /// is pending as an IRQ of the core. They are all routed to core 0 by
+    /// default.
+    pub fn is_gpu_pending(&self) -> bool {
+        self.registers.IRQ_SOURCE[self.core].has_mask(GPU_PENDING)
+    }
+
+    /// Sets the bits of `value` in mailbox `mailbox` of core `core`, raising
+    /// the mailbox interrupt there if it is enabled.
+    pub fn send(&mut self, core: usize, mailbox: usize, value: u32) {
+        self.registers.MAILBOX_SET[core][mailbox].write(value);
+    }
+
+    /// Returns the value of mailbox `mailbox` of the core.
+    pub fn mailbox(&self, mailbox: usize) -> u32 {
+        self.registers.MAILBOX_CLR[self.core][mailbox].read()
+    }
+
+    /// Clears the bits of `value` in mailbox `mailbox` of the core. The
+    /// mailbox interrupt stays pending while any bit is set.
+    pub fn clear(&mut self, mailbox: usize, value: u32) {
+        self.registers.MAILBOX_CLR[self.core][mailbox].write(value);
+    }
+}
@@ -0,0 +1,365 @@
+use core::mem::size_of;
+
+use volatile::prelude::*;
+use volatile::{ReadVolatile, Reserved, Volatile};
+
+use crate::common::IO_BASE;
+
+/// The base address for the ARM-side `Mailbox 0` registers.
+const MAILBOX_REG_BASE: usize = IO_BASE + 0xB880;
+
+/// The channel of the property tag interface (ARM to VideoCore).
+pub const PROPERTY_CHANNEL: u8 = 8;
+
+/// The alias under which the VideoCore sees ARM physical memory uncached.
+const BUS_ALIAS: u32 = 0xC000_0000;
+
+/// The size of a data cache line.
+const CACHE_LINE: usize = 64;
+
+/// Enum representing bit fields of the mailbox `STATUS` register.
+#[repr(u32)]
+enum Status {
+    Empty = 1 << 30,
+    Full = 1 << 31,
+}
+
+/// The code of a buffer holding a request.
+const REQUEST: u32 = 0;
+
+/// The code of a buffer the firmware processed successfully.
+const RESPONSE_SUCCESS: u32 = 0x8000_0000;
+
+/// The bit set in a tag's code once the firmware has filled its response.
+const TAG_RESPONSE: u32 = 1 << 31;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    READ: ReadVolatile<u32>,
+    __r0: [Reserved<u32>; 3],
+    PEEK: ReadVolatile<u32>,
+    SENDER: ReadVolatile<u32>,
+    STATUS: ReadVolatile<u32>,
+    CONFIG: Volatile<u32>,
+    // `Mailbox 1`, the one the ARM writes to.
+    WRITE: Volatile<u32>,
+    __r1: [Reserved<u32>; 5],
+    WRITE_STATUS: ReadVolatile<u32>,
+}
+
+/// Property tags understood by the firmware.
+#[allow(non_snake_case)]
+#[allow(non_upper_case_globals)]
+pub mod Tag {
+    pub const GetFirmwareRevision: u32 = 0x0000_0001;
+    pub const GetBoardModel: u32 = 0x0001_0001;
+    pub const GetBoardRevision: u32 = 0x0001_0002;
+    pub const GetBoardMacAddress: u32 = 0x0001_0003;
+    pub const GetBoardSerial: u32 = 0x0001_0004;
+    pub const GetArmMemory: u32 = 0x0001_0005;
+    pub const GetVcMemory: u32 = 0x0001_0006;
+    pub const GetPowerState: u32 = 0x0002_0001;
+    pub const SetPowerState: u32 = 0x0002_8001;
+    pub const GetClockRate: u32 = 0x0003_0002;
+    pub const GetMaxClockRate: u32 = 0x0003_0004;
+    pub const GetTemperature: u32 = 0x0003_0006;
+    pub const GetMaxTemperature: u32 = 0x0003_000a;
+    pub const AllocateBuffer: u32 = 0x0004_0001;
+    pub const GetPitch: u32 = 0x0004_0008;
+    pub const SetPhysicalSize: u32 = 0x0004_8003;
+    pub const SetVirtualSize: u32 = 0x0004_8004;
+    pub const SetDepth: u32 = 0x0004_8005;
+    pub const SetPixelOrder: u32 = 0x0004_8006;
+    pub const SetVirtualOffset: u32 = 0x0004_8009;
+}
+
+/// Clocks whose rate can be queried.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Clock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+/// Devices whose power state can be queried and changed.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Device {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+/// The power state of a `Device`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PowerState {
+    /// Whether the device is powered on.
+    pub on: bool,
+    /// Whether the device exists.
+    pub exists: bool,
+}
+
+/// An error returned by a property request.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Error {
+    /// The firmware could not parse the request buffer.
+    BadRequest,
+    /// The firmware did not fill in the response to the tag, e.g. because it
+    /// does not know it.
+    Unanswered,
+}
+
+/// The `Mailbox 0`/`Mailbox 1` pair used to exchange messages with the
+/// VideoCore firmware.
+///
+/// Handles are not synchronized: a single caller at a time may use the
+/// mailboxes, through a `Mailbox` or any function of this module. The
+/// kernel serializes its callers with `mailbox::with()`.
+pub struct Mailbox {
+    registers: &'static mut Registers,
+}
+
+impl Mailbox {
+    /// Returns a new handle to the mailboxes.
+    pub fn new() -> Mailbox {
+        Mailbox {
+            registers: unsafe { &mut *(MAILBOX_REG_BASE as *mut Registers) },
+        }
+    }
+
+    /// Sends `data` (whose low 4 bits must be zero) to `channel` and waits for
+    /// the firmware to reply on the same channel. Returns the reply, with the
+    /// channel number masked out.
+    pub fn call(&mut self, channel: u8, data: u32) -> u32 {
+        while self.registers.WRITE_STATUS.has_mask(Status::Full as u32) {}
+        self.registers.WRITE.write((data & !0xf) | (channel as u32 & 0xf));
+
+        loop {
+            while self.registers.STATUS.has_mask(Status::Empty as u32) {}
+            let reply = self.registers.READ.read();
+            if reply & 0xf == channel as u32 {
+                return reply & !0xf;
+            }
+        }
+    }
+
+    /// Passes the property buffer `buf` to the firmware over the property
+    /// channel and waits for it to be processed in place.
+    ///
+    /// `buf` must be 16-byte aligned and hold a well-formed request: its size
+    /// in bytes, the request code, a sequence of tags, and the end tag.
+    pub fn property(&mut self, buf: &mut [u32]) -> Result<(), Error> {
+        let addr = buf.as_mut_ptr() as usize;
+        let len = buf.len() * size_of::<u32>();
+
+        // The firmware accesses memory behind the ARM's caches.
+        clean_invalidate(addr, len);
+        self.call(PROPERTY_CHANNEL, addr as u32 | BUS_ALIAS);
+        clean_invalidate(addr, len);
+
+        match buf[1] {
+            RESPONSE_SUCCESS => Ok(()),
+            _ => Err(Error::BadRequest),
+        }
+    }
+}
+
+/// The capacity of a `PropertyBuffer` in words.
+const PROPERTY_BUFFER_WORDS: usize = 64;
+
+/// A property request buffer holding a sequence of tags, each with its value
+/// buffer. The firmware overwrites the value buffers with its responses.
+#[repr(C)]
+#[repr(align(16))]
+pub struct PropertyBuffer {
+    words: [u32; PROPERTY_BUFFER_WORDS],
+    len: usize,
+}
+
+/// Identifies a tag in a `PropertyBuffer`.
+#[derive(Debug, Copy, Clone)]
+pub struct TagIndex(usize);
+
+impl PropertyBuffer {
+    /// Returns an empty request buffer.
+    pub fn new() -> PropertyBuffer {
+        let mut words = [0; PROPERTY_BUFFER_WORDS];
+        words[1] = REQUEST;
+        PropertyBuffer { words, len: 2 }
+    }
+
+    /// Appends the tag `tag` whose value buffer is initialized with `values`.
+    /// The value buffer must be large enough for the response too.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is full.
+    pub fn push(&mut self, tag: u32, values: &[u32]) -> TagIndex {
+        let start = self.len;
+        // The end tag must fit too.
+        assert!(start + 3 + values.len() < PROPERTY_BUFFER_WORDS, "property buffer is full");
+
+        self.words[start] = tag;
+        self.words[start + 1] = (values.len() * size_of::<u32>()) as u32;
+        self.words[start + 2] = REQUEST;
+        self.words[start + 3..start + 3 + values.len()].copy_from_slice(values);
+        self.len = start + 3 + values.len();
+        TagIndex(start)
+    }
+
+    /// Returns the words of the buffer, end tag included.
+    fn words(&mut self) -> &mut [u32] {
+        self.words[0] = ((self.len + 1) * size_of::<u32>()) as u32;
+        self.words[self.len] = 0;
+        &mut self.words[..self.len + 1]
+    }
+
+    /// Passes the buffer to the firmware and waits for the responses.
+    pub fn send(&mut self) -> Result<(), Error> {
+        Mailbox::new().property(self.words())
+    }
+
+    /// Returns the value buffer of the tag `index` after `send()`. Fails if
+    /// the firmware did not respond to that tag.
+    pub fn response(&self, index: TagIndex) -> Result<&[u32], Error> {
+        let TagIndex(start) = index;
+        if self.words[start + 2] & TAG_RESPONSE == 0 {
+            return Err(Error::Unanswered);
+        }
+        let len = self.words[start + 1] as usize / size_of::<u32>();
+        Ok(&self.words[start + 3..start + 3 + len])
+    }
+}
+
+/// Sends the request `tag` with the request values `value` and returns the
+/// response values. `value` must be large enough for both the request and the
+/// response.
+pub fn request<T: AsRef<[u32]> + AsMut<[u32]>>(tag: u32, mut value: T) -> Result<T, Error> {
+    let mut buf = PropertyBuffer::new();
+    let index = buf.push(tag, value.as_ref());
+    buf.send()?;
+    value.as_mut().copy_from_slice(buf.response(index)?);
+    Ok(value)
+}
+
+/// Returns the firmware revision.
+pub fn firmware_revision() -> Result<u32, Error> {
+    request(Tag::GetFirmwareRevision, [0u32; 1]).map(|v| v[0])
+}
+
+/// Returns the board revision code.
+pub fn board_revision() -> Result<u32, Error> {
+    request(Tag::GetBoardRevision, [0u32; 1]).map(|v| v[0])
+}
+
+/// Returns the board serial number.
+pub fn board_serial() -> Result<u64, Error> {
+    request(Tag::GetBoardSerial, [0u32; 2]).map(|v| (v[1] as u64) << 32 | v[0] as u64)
+}
+
+/// Returns the `(base, size)` of the memory assigned to the ARM cores.
+pub fn arm_memory() -> Result<(usize, usize), Error> {
+    request(Tag::GetArmMemory, [0u32; 2]).map(|v| (v[0] as usize, v[1] as usize))
+}
+
+/// Returns the `(base, size)` of the memory assigned to the VideoCore.
+pub fn vc_memory() -> Result<(usize, usize), Error> {
+    request(Tag::GetVcMemory, [0u32; 2]).map(|v| (v[0] as usize, v[1] as usize))
+}
+
+/// Returns the current rate of `clock` in Hz.
+pub fn clock_rate(clock: Clock) -> Result<u32, Error> {
+    request(Tag::GetClockRate, [clock as u32, 0]).map(|v| v[1])
+}
+
+/// Returns the maximum rate of `clock` in Hz.
+pub fn max_clock_rate(clock: Clock) -> Result<u32, Error> {
+    request(Tag::GetMaxClockRate, [clock as u32, 0]).map(|v| v[1])
+}
+
+/// Returns the SoC temperature in thousandths of a degree Celsius.
+pub fn temperature() -> Result<u32, Error> {
+    request(Tag::GetTemperature, [0u32, 0]).map(|v| v[1])
+}
+
+/// Returns the temperature, in thousandths of a degree Celsius, above which
+/// the firmware throttles the clocks.
+pub fn max_temperature() -> Result<u32, Error> {
+    request(Tag::GetMaxTemperature, [0u32, 0]).map(|v| v[1])
+}
+
+fn decode_power_state(state: u32) -> PowerState {
+    PowerState {
+        on: state & 1 != 0,
+        exists: state & 2 == 0,
+    }
+}
+
+/// Returns the power state of `device`.
+pub fn power_state(device: Device) -> Result<PowerState, Error> {
+    request(Tag::GetPowerState, [device as u32, 0]).map(|v| decode_power_state(v[1]))
+}
+
+/// Powers `device` on or off, waiting for the device to become stable if
+/// `wait` is `true`. Returns the new power state.
+pub fn set_power_state(device: Device, on: bool, wait: bool) -> Result<PowerState, Error> {
+    let state = (on as u32) | (wait as u32) << 1;
+    request(Tag::SetPowerState, [device as u32, state]).map(|v| decode_power_state(v[1]))
+}
+
+/// Cleans and invalidates the data cache lines covering `[addr, addr + len)`.
+fn clean_invalidate(addr: usize, len: usize) {
+    let mut line = addr & !(CACHE_LINE - 1);
+    while line < addr + len {
+        #[cfg(target_arch = "aarch64")]
+        unsafe { asm!("dc civac, $0" :: "r"(line) :: "volatile") };
+        line += CACHE_LINE;
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe { asm!("dsb sy" :::: "volatile") };
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PropertyBuffer, Tag};
+
+    #[test]
+    fn test_property_buffer_layout() {
+        let mut buf = PropertyBuffer::new();
+        buf.push(Tag::GetArmMemory, &[0, 0]);
+        buf.push(Tag::GetClockRate, &[3, 0]);
+        assert_eq!(buf.words(), &[
+            13 * 4, 0,
+            Tag::GetArmMemory, 8, 0, 0, 0,
+            Tag::GetClockRate, 8, 0, 3, 0,
+            0,
+        ]);
+    }
+
+    #[test]
+    fn test_property_buffer_response() {
+        let mut buf = PropertyBuffer::new();
+        let index = buf.push(Tag::GetBoardRevision, &[0]);
+        assert!(buf.response(index).is_err());
+
+        // What the firmware writes back.
+        buf.words[4] = (1 << 31) | 4;
+        buf.words[5] = 0xa02082;
+        assert_eq!(buf.response(index), Ok(&[0xa02082][..]));
+    }
+}
@@ -0,0 +1,296 @@
+use core::fmt;
+use core::time::Duration;
+
+use shim::const_assert_size;
+use shim::io;
+
+use volatile::prelude::*;
+use volatile::{ReadVolatile, Reserved, Volatile, WriteVolatile};
+
+use crate::common::IO_BASE;
+use crate::gpio::{Function, Gpio};
+use crate::timer;
+
+/// The base address for the PL011 `UART0` registers.
+const UART0_REG_BASE: usize = IO_BASE + 0x201000;
+
+/// The frequency of `UARTCLK`, as configured by the firmware
+/// (`init_uart_clock`).
+pub const UART_CLOCK: u32 = 48_000_000;
+
+/// The default baud rate.
+pub const DEFAULT_BAUD: u32 = 115200;
+
+/// Enum representing bit fields of the `UART_FR` register.
+#[repr(u32)]
+enum FrStatus {
+    RxFifoEmpty = 1 << 4,
+    TxFifoFull = 1 << 5,
+    Busy = 1 << 3,
+}
+
+/// Enum representing the error bits of the `UART_DR` register.
+#[repr(u32)]
+enum DrStatus {
+    Framing = 1 << 8,
+    Parity = 1 << 9,
+    Break = 1 << 10,
+    Overrun = 1 << 11,
+}
+
+/// Enum representing bit fields of the `UART_LCRH` register.
+#[repr(u32)]
+enum LcrhStatus {
+    EnableFifos = 1 << 4,
+    WordLength8 = 0b11 << 5,
+}
+
+/// Enum representing bit fields of the `UART_CR` register.
+#[repr(u32)]
+enum CrStatus {
+    Enable = 1 << 0,
+    TxEnable = 1 << 8,
+    RxEnable = 1 << 9,
+}
+
+/// The receive interrupt bits of `UART_IMSC`, `UART_MIS` and `UART_ICR`:
+/// receive (`RXIM`) and receive timeout (`RTIM`).
+const RX_INTERRUPTS: u32 = (1 << 4) | (1 << 6);
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // Declare the PL011 registers from page 177.
+    DR: Volatile<u32>,
+    RSRECR: Volatile<u32>,
+    __r0: [Reserved<u32>; 4],
+    FR: ReadVolatile<u32>,
+    __r1: Reserved<u32>,
+    ILPR: Volatile<u32>,
+    IBRD: Volatile<u32>,
+    FBRD: Volatile<u32>,
+    LCRH: Volatile<u32>,
+    CR: Volatile<u32>,
+    IFLS: Volatile<u32>,
+    IMSC: Volatile<u32>,
+    RIS: ReadVolatile<u32>,
+    MIS: ReadVolatile<u32>,
+    ICR: WriteVolatile<u32>,
+    DMACR: Volatile<u32>,
+}
+
+const_assert_size!(Registers, 0x7E20104C - 0x7E201000);
+
+/// An error reported by the receiver for a received byte.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RxError {
+    /// The byte did not have a valid stop bit.
+    Framing,
+    /// The parity of the byte did not match the configured parity.
+    Parity,
+    /// The line was held low for longer than a full frame.
+    Break,
+    /// The receive FIFO was full and a byte was lost.
+    Overrun,
+}
+
+impl From<RxError> for io::Error {
+    fn from(error: RxError) -> io::Error {
+        match error {
+            RxError::Framing => io::Error::new(io::ErrorKind::InvalidData, "framing error"),
+            RxError::Parity => io::Error::new(io::ErrorKind::InvalidData, "parity error"),
+            RxError::Break => io::Error::new(io::ErrorKind::InvalidData, "break condition"),
+            RxError::Overrun => io::Error::new(io::ErrorKind::Other, "receive overrun"),
+        }
+    }
+}
+
+/// The Raspberry Pi's PL011 `UART0`.
+pub struct Pl011 {
+    registers: &'static mut Registers,
+    timeout: Option<Duration>,
+}
+
+impl Pl011 {
+    /// Initializes the PL011 with 8 data bits, no parity, one stop bit and
+    /// FIFOs enabled at `baud` bits per second, setting GPIO pins 14 and 15
+    /// to alternative function 0 (TXD0/RXD0), and finally enabling the UART
+    /// transmitter and receiver.
+    ///
+    /// Since the PL011 and the mini UART share pins 14 and 15, only one of
+    /// them can be used at a time.
+    ///
+    /// By default, reads will never time out. To set a read timeout, use
+    /// `set_read_timeout()`.
+    pub fn new(baud: u32) -> Pl011 {
+        let registers = unsafe { &mut *(UART0_REG_BASE as *mut Registers) };
+
+        // The UART must be disabled and idle before it is reconfigured.
+        registers.CR.write(0);
+        while registers.FR.has_mask(FrStatus::Busy as u32) {}
+
+        Pl011::claim_pins();
+
+        let (ibrd, fbrd) = Self::divisor(baud);
+        registers.ICR.write(0x7ff);
+        registers.IBRD.write(ibrd);
+        registers.FBRD.write(fbrd);
+        // LCRH must be written after IBRD/FBRD to latch them.
+        registers.LCRH.write(LcrhStatus::WordLength8 as u32 | LcrhStatus::EnableFifos as u32);
+        registers.IMSC.write(0);
+        registers.CR.write(CrStatus::Enable as u32 | CrStatus::TxEnable as u32
+            | CrStatus::RxEnable as u32);
+
+        Pl011 { registers, timeout: None }
+    }
+
+    /// Sets GPIO pins 14 and 15 to alternative function 0 (TXD0/RXD0), e.g.
+    /// to hand them back to an initialized PL011 after the mini UART took
+    /// them over.
+    pub fn claim_pins() {
+        Gpio::new(14).into_alt(Function::Alt0);
+        Gpio::new(15).into_alt(Function::Alt0);
+    }
+
+    /// Returns the integer and fractional baud rate divisors for `baud`:
+    /// `UART_CLOCK / (16 * baud)`, the fraction being in 64ths and rounded to
+    /// the nearest.
+    fn divisor(baud: u32) -> (u32, u32) {
+        // 64 * UART_CLOCK / (16 * baud), rounded
+        let div = (4 * UART_CLOCK as u64 + baud as u64 / 2) / baud as u64;
+        ((div >> 6) as u32, (div & 0x3f) as u32)
+    }
+
+    /// Set the read timeout to `t` duration.
+    pub fn set_read_timeout(&mut self, t: Duration) {
+        self.timeout = Some(t);
+    }
+
+    /// Enables or disables the receive interrupts. While enabled, the UART
+    /// raises `Interrupt::Uart` when its receive FIFO fills up or when bytes
+    /// have been sitting in it for a while.
+    pub fn set_rx_interrupt(&mut self, enabled: bool) {
+        if enabled {
+            self.registers.IMSC.or_mask(RX_INTERRUPTS);
+        } else {
+            self.registers.IMSC.and_mask(!RX_INTERRUPTS);
+        }
+    }
+
+    /// Write the byte `byte`. This method blocks until there is space available
+    /// in the output FIFO.
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.registers.FR.has_mask(FrStatus::TxFifoFull as u32) {
+            // Do nothing
+        }
+        self.registers.DR.write(byte as u32);
+    }
+
+    /// Returns `true` if there is at least one byte ready to be read. If this
+    /// method returns `true`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately. This method does not block.
+    pub fn has_byte(&self) -> bool {
+        !self.registers.FR.has_mask(FrStatus::RxFifoEmpty as u32)
+    }
+
+    /// Blocks until there is a byte ready to read. If a read timeout is set,
+    /// this method blocks for at most that amount of time. Otherwise, this
+    /// method blocks indefinitely until there is a byte to read.
+    ///
+    /// Returns `Ok(())` if a byte is ready to read. Returns `Err(())` if the
+    /// timeout expired while waiting for a byte to be ready. If this method
+    /// returns `Ok(())`, a subsequent call to `read_byte` is guaranteed to
+    /// return immediately.
+    pub fn wait_for_byte(&self) -> Result<(), ()> {
+        let beginning = timer::current_time();
+        while !self.has_byte() {
+            if let Some(duration) = self.timeout {
+                if timer::current_time() > beginning + duration {
+                    return Err(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a byte, reporting any receive error flagged for it. Blocks
+    /// indefinitely until a byte is ready to be read.
+    pub fn try_read_byte(&mut self) -> Result<u8, RxError> {
+        while !self.has_byte() {
+            // Do nothing
+        }
+
+        let data = self.registers.DR.read();
+        if data & DrStatus::Overrun as u32 != 0 {
+            Err(RxError::Overrun)
+        } else if data & DrStatus::Break as u32 != 0 {
+            Err(RxError::Break)
+        } else if data & DrStatus::Parity as u32 != 0 {
+            Err(RxError::Parity)
+        } else if data & DrStatus::Framing as u32 != 0 {
+            Err(RxError::Framing)
+        } else {
+            Ok(data as u8)
+        }
+    }
+
+    /// Reads a byte, ignoring receive errors. Blocks indefinitely until a byte
+    /// is ready to be read.
+    pub fn read_byte(&mut self) -> u8 {
+        while !self.has_byte() {
+            // Do nothing
+        }
+        self.registers.DR.read() as u8
+    }
+}
+
+// A b'\r' byte is written before any b'\n' byte, as for `MiniUart`.
+impl fmt::Write for Pl011 {
+    fn write_str(&mut self, string: &str) -> Result<(), fmt::Error> {
+        for byte in string.bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+mod pl011_io {
+    use super::io;
+    use super::Pl011;
+
+    // `io::Read::read()` waits at most the read timeout for the first byte,
+    // then reads as many bytes as are available. A receive error on any byte
+    // fails the read.
+    impl io::Read for Pl011 {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+            if self.wait_for_byte().is_err() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "timeout"));
+            }
+
+            let mut read = 0;
+            while self.has_byte() && read < buf.len() {
+                buf[read] = self.try_read_byte()?;
+                read += 1;
+            }
+            Ok(read)
+        }
+    }
+
+    impl io::Write for Pl011 {
+        fn write(&mut self, data: &[u8]) -> Result<usize, io::Error> {
+            for &byte in data {
+                self.write_byte(byte);
+            }
+            Ok(data.len())
+        }
+
+        /// Waits until every written byte has been sent.
+        fn flush(&mut self) -> Result<(), io::Error> {
+            while self.registers.FR.has_mask(FrStatus::Busy as u32) {}
+            Ok(())
+        }
+    }
+}
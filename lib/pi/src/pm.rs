@@ -0,0 +1,156 @@
+use core::time::Duration;
+
+use shim::const_assert_size;
+
+use volatile::prelude::*;
+use volatile::{Reserved, Volatile};
+
+use crate::common::IO_BASE;
+
+/// The base address for the power management registers `PM_RSTC`,
+/// `PM_RSTS` and `PM_WDOG`.
+const PM_REG_BASE: usize = IO_BASE + 0x10001C;
+
+/// The password every power management write must carry in its top byte.
+const PM_PASSWORD: u32 = 0x5a << 24;
+
+/// The `WRCFG` field of `PM_RSTC`: what happens when the watchdog expires.
+const RSTC_WRCFG_MASK: u32 = 0b11 << 4;
+const RSTC_WRCFG_FULL_RESET: u32 = 0b10 << 4;
+/// Written to `PM_RSTC` to stop the watchdog.
+const RSTC_RESET: u32 = 0x102;
+
+/// The boot partition bits of `PM_RSTS`: every other bit from bit 0 to 10.
+/// The firmware halts instead of booting when they select partition 63.
+const RSTS_PARTITION_MASK: u32 = 0x555;
+const RSTS_HALT_PARTITION: u32 = 63;
+
+/// The flags of `PM_RSTS` telling what caused the last reset.
+const RSTS_HAD_POWER_ON: u32 = 1 << 12;
+const RSTS_HAD_WATCHDOG: u32 = 0b111 << 4;
+
+/// The watchdog counts down at 65536 ticks per second, from at most 2^20 - 1.
+const WDOG_TICKS_PER_SEC: u64 = 1 << 16;
+const WDOG_TIME_MASK: u32 = 0xfffff;
+
+/// The longest timeout the watchdog can be armed with, about 16 seconds.
+pub const MAX_TIMEOUT: Duration = Duration::from_micros(
+    WDOG_TIME_MASK as u64 * 1_000_000 / WDOG_TICKS_PER_SEC);
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    RSTC: Volatile<u32>,
+    RSTS: Volatile<u32>,
+    WDOG: Volatile<u32>,
+    __r0: Reserved<u32>,
+}
+
+const_assert_size!(Registers, 0x7E10002C - 0x7E10001C);
+
+/// What caused the last reset, from `PM_RSTS`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ResetReason {
+    /// The board was powered on.
+    PowerOn,
+    /// The watchdog expired. This includes resets requested with `reboot()`.
+    Watchdog,
+    /// The reset flags do not tell: the raw `PM_RSTS` value.
+    Unknown(u32),
+}
+
+fn registers() -> &'static mut Registers {
+    unsafe { &mut *(PM_REG_BASE as *mut Registers) }
+}
+
+/// Returns `partition` spread over the partition bits of `PM_RSTS`.
+fn partition_bits(partition: u32) -> u32 {
+    (0..6).fold(0, |bits, i| bits | ((partition >> i) & 1) << (2 * i))
+}
+
+/// Returns the value of the watchdog counter for `timeout`, saturated to
+/// `MAX_TIMEOUT`.
+fn ticks(timeout: Duration) -> u32 {
+    let ticks = timeout.as_micros() * WDOG_TICKS_PER_SEC as u128 / 1_000_000;
+    ticks.min(WDOG_TIME_MASK as u128) as u32
+}
+
+/// Resets the board through the watchdog, asking the firmware to boot from
+/// `partition`.
+fn reset(partition: u32) -> ! {
+    let registers = registers();
+    let rsts = registers.RSTS.read() & !RSTS_PARTITION_MASK;
+    registers.RSTS.write(PM_PASSWORD | rsts | partition_bits(partition));
+
+    registers.WDOG.write(PM_PASSWORD | 10);
+    let rstc = registers.RSTC.read() & !RSTC_WRCFG_MASK;
+    registers.RSTC.write(PM_PASSWORD | rstc | RSTC_WRCFG_FULL_RESET);
+    loop {}
+}
+
+/// Reboots the board. The reset happens a few microseconds after the call.
+pub fn reboot() -> ! {
+    reset(0)
+}
+
+/// Halts the board: it is reset, but the firmware stops instead of loading
+/// the kernel again. Only a power cycle brings it back.
+pub fn halt() -> ! {
+    reset(RSTS_HALT_PARTITION)
+}
+
+/// Returns the reason of the last reset.
+pub fn reset_reason() -> ResetReason {
+    let rsts = registers().RSTS.read();
+    if rsts & RSTS_HAD_POWER_ON != 0 {
+        ResetReason::PowerOn
+    } else if rsts & RSTS_HAD_WATCHDOG != 0 {
+        ResetReason::Watchdog
+    } else {
+        ResetReason::Unknown(rsts)
+    }
+}
+
+/// The watchdog timer. Once started, it resets the board unless it is
+/// petted before its timeout expires.
+pub struct Watchdog {
+    registers: &'static mut Registers,
+    timeout: u32,
+}
+
+impl Watchdog {
+    /// Returns a handle to the watchdog. The watchdog is left as is.
+    pub fn new() -> Watchdog {
+        Watchdog { registers: registers(), timeout: ticks(MAX_TIMEOUT) }
+    }
+
+    /// Starts the watchdog: the board is reset if it is not petted within
+    /// `timeout`, saturated to `MAX_TIMEOUT`.
+    pub fn start(&mut self, timeout: Duration) {
+        self.timeout = ticks(timeout);
+        self.pet();
+        let rstc = self.registers.RSTC.read() & !RSTC_WRCFG_MASK;
+        self.registers.RSTC.write(PM_PASSWORD | rstc | RSTC_WRCFG_FULL_RESET);
+    }
+
+    /// Restarts the countdown from the timeout given to `start()`.
+    pub fn pet(&mut self) {
+        self.registers.WDOG.write(PM_PASSWORD | self.timeout);
+    }
+
+    /// Stops the watchdog.
+    pub fn stop(&mut self) {
+        self.registers.RSTC.write(PM_PASSWORD | RSTC_RESET);
+    }
+
+    /// Returns `true` if the watchdog is counting down.
+    pub fn is_running(&self) -> bool {
+        self.registers.RSTC.read() & RSTC_WRCFG_MASK != 0
+    }
+
+    /// Returns the time left before the watchdog expires.
+    pub fn remaining(&self) -> Duration {
+        let ticks = (self.registers.WDOG.read() & WDOG_TIME_MASK) as u64;
+        Duration::from_micros(ticks * 1_000_000 / WDOG_TICKS_PER_SEC)
+    }
+}
@@ -0,0 +1,329 @@
+use shim::const_assert_size;
+
+use volatile::prelude::*;
+use volatile::{Reserved, Volatile};
+
+use crate::common::IO_BASE;
+use crate::gpio::{Function, Gpio};
+
+/// The base address for the `PWM` registers.
+const PWM_REG_BASE: usize = IO_BASE + 0x20C000;
+
+/// The base address for the PWM clock manager registers, `CM_PWMCTL` and
+/// `CM_PWMDIV`.
+const CM_PWM_REG_BASE: usize = IO_BASE + 0x1010A0;
+
+/// The bus address of the PWM FIFO, for a DMA engine to write to.
+pub const FIFO_BUS_ADDR: usize = 0x7E20C018;
+
+/// The frequency of the oscillator the PWM clock is derived from.
+pub const OSCILLATOR_CLOCK: u32 = 19_200_000;
+
+/// The password clock manager writes must carry in their top byte.
+const CM_PASSWORD: u32 = 0x5a << 24;
+
+/// Enum representing bit fields of the `CM_PWMCTL` register.
+#[repr(u32)]
+enum CmCtl {
+    SourceOscillator = 1,
+    Enable = 1 << 4,
+    Busy = 1 << 7,
+    Mash1 = 1 << 9,
+}
+
+/// Enum representing bit fields of the `CTL` register, for channel 1. The
+/// bits of channel 2 are 8 bits higher.
+#[repr(u32)]
+enum Ctl {
+    Enable = 1 << 0,
+    UseFifo = 1 << 5,
+    ClearFifo = 1 << 6,
+    MarkSpace = 1 << 7,
+}
+
+/// Enum representing bit fields of the `STA` register.
+#[repr(u32)]
+enum Sta {
+    FifoFull = 1 << 0,
+    WriteError = 1 << 2,
+    ReadError = 1 << 3,
+    BusError = 1 << 8,
+}
+
+/// The `DMAC` enable bit, and the default DREQ and PANIC thresholds.
+const DMAC_ENABLE: u32 = 1 << 31;
+const DMAC_THRESHOLDS: u32 = 7 << 8 | 7;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // Declare the PWM registers from page 141.
+    CTL: Volatile<u32>,
+    STA: Volatile<u32>,
+    DMAC: Volatile<u32>,
+    __r0: Reserved<u32>,
+    RNG1: Volatile<u32>,
+    DAT1: Volatile<u32>,
+    FIF1: Volatile<u32>,
+    __r1: Reserved<u32>,
+    RNG2: Volatile<u32>,
+    DAT2: Volatile<u32>,
+}
+
+const_assert_size!(Registers, 0x7E20C028 - 0x7E20C000);
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct ClockRegisters {
+    CTL: Volatile<u32>,
+    DIV: Volatile<u32>,
+}
+
+/// An error configuring the PWM clock.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Error {
+    /// The rate is faster than the divider can derive from the oscillator:
+    /// over 19.2 MHz, or over 9.6 MHz for a fractional divider, which needs
+    /// an integer part of 2 at least.
+    ClockTooFast,
+    /// The rate is null, or slower than the largest divider, 4095, allows.
+    ClockTooSlow,
+}
+
+/// Returns the integer and fractional (in 4096ths) parts of the divider
+/// deriving `hz` from the oscillator.
+fn divider(hz: u32) -> Result<(u32, u32), Error> {
+    if hz == 0 {
+        return Err(Error::ClockTooSlow);
+    }
+    let divi = OSCILLATOR_CLOCK / hz;
+    let divf = ((OSCILLATOR_CLOCK % hz) as u64 * 4096 / hz as u64) as u32;
+    match divi {
+        0 => Err(Error::ClockTooFast),
+        // MASH noise shaping, used for fractional dividers, needs `divi >= 2`.
+        1 if divf != 0 => Err(Error::ClockTooFast),
+        divi if divi > 0xfff => Err(Error::ClockTooSlow),
+        _ => Ok((divi, divf)),
+    }
+}
+
+/// Returns the number of clocks out of `range` a duty cycle of `percent`
+/// percent, clamped to 100, is high for.
+fn duty(range: u32, percent: u32) -> u32 {
+    (range as u64 * percent.min(100) as u64 / 100) as u32
+}
+
+/// One of the two PWM channels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Channel {
+    /// `PWM0`, on GPIO pins 12, 18 and 40 (left headphone channel).
+    Pwm0,
+    /// `PWM1`, on GPIO pins 13, 19 and 45 (right headphone channel).
+    Pwm1,
+}
+
+impl Channel {
+    /// Returns the shift of the channel's bits in `CTL`.
+    fn shift(self) -> u32 {
+        match self {
+            Channel::Pwm0 => 0,
+            Channel::Pwm1 => 8,
+        }
+    }
+}
+
+/// How a channel spreads the high part of each period.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+    /// The output is high for the first `data` clocks of every `range`
+    /// clocks, like a classic PWM.
+    MarkSpace,
+    /// The high clocks are spread as evenly as possible over the period,
+    /// which is easier to filter into an analog level.
+    Balanced,
+}
+
+/// Routes the PWM output for `pin` to it and returns the channel it carries,
+/// or `None` if `pin` has no PWM function.
+pub fn route(pin: u8) -> Option<Channel> {
+    let (channel, function) = match pin {
+        12 => (Channel::Pwm0, Function::Alt0),
+        13 => (Channel::Pwm1, Function::Alt0),
+        18 => (Channel::Pwm0, Function::Alt5),
+        19 => (Channel::Pwm1, Function::Alt5),
+        40 => (Channel::Pwm0, Function::Alt0),
+        45 => (Channel::Pwm1, Function::Alt0),
+        _ => return None,
+    };
+    Gpio::new(pin).into_alt(function);
+    Some(channel)
+}
+
+/// The Raspberry Pi's PWM controller and its clock.
+pub struct Pwm {
+    registers: &'static mut Registers,
+    clock: &'static mut ClockRegisters,
+}
+
+impl Pwm {
+    /// Stops both channels and starts the PWM clock at `hz`, derived from the
+    /// 19.2 MHz oscillator. A channel with a range of `r` then has a period of
+    /// `r / hz` seconds. Fails if `hz` cannot be derived from the oscillator
+    /// (see `set_clock()`).
+    ///
+    /// No pin is routed to the channels: use `route()` for that.
+    pub fn new(hz: u32) -> Result<Pwm, Error> {
+        let mut pwm = Pwm {
+            registers: unsafe { &mut *(PWM_REG_BASE as *mut Registers) },
+            clock: unsafe { &mut *(CM_PWM_REG_BASE as *mut ClockRegisters) },
+        };
+        pwm.registers.CTL.write(0);
+        pwm.set_clock(hz)?;
+        Ok(pwm)
+    }
+
+    /// Restarts the PWM clock at `hz`. Integer dividers are used as such;
+    /// fractional ones are approximated with MASH noise shaping. Fails,
+    /// leaving the clock as is, if `hz` is null, over 9.6 MHz but not
+    /// 19.2 MHz exactly, or under 19.2 MHz / 4095.
+    pub fn set_clock(&mut self, hz: u32) -> Result<(), Error> {
+        let (divi, divf) = divider(hz)?;
+        let mash = if divf != 0 { CmCtl::Mash1 as u32 } else { 0 };
+
+        // The clock must be stopped, and idle, before it is reconfigured.
+        self.clock.CTL.write(CM_PASSWORD | CmCtl::SourceOscillator as u32);
+        while self.clock.CTL.has_mask(CmCtl::Busy as u32) {}
+
+        self.clock.DIV.write(CM_PASSWORD | divi << 12 | divf);
+        self.clock.CTL.write(CM_PASSWORD | mash | CmCtl::SourceOscillator as u32);
+        self.clock.CTL.write(CM_PASSWORD | mash | CmCtl::SourceOscillator as u32
+            | CmCtl::Enable as u32);
+        Ok(())
+    }
+
+    fn range_register(&mut self, channel: Channel) -> &mut Volatile<u32> {
+        match channel {
+            Channel::Pwm0 => &mut self.registers.RNG1,
+            Channel::Pwm1 => &mut self.registers.RNG2,
+        }
+    }
+
+    fn data_register(&mut self, channel: Channel) -> &mut Volatile<u32> {
+        match channel {
+            Channel::Pwm0 => &mut self.registers.DAT1,
+            Channel::Pwm1 => &mut self.registers.DAT2,
+        }
+    }
+
+    /// Starts `channel` in `mode` with a period of `range` clocks, and a duty
+    /// cycle of 0.
+    pub fn enable(&mut self, channel: Channel, mode: Mode, range: u32) {
+        let shift = channel.shift();
+        self.registers.CTL.and_mask(!(0xff << shift));
+        self.range_register(channel).write(range);
+        self.data_register(channel).write(0);
+
+        let mode = match mode {
+            Mode::MarkSpace => Ctl::MarkSpace as u32,
+            Mode::Balanced => 0,
+        };
+        self.registers.CTL.or_mask((mode | Ctl::Enable as u32) << shift);
+    }
+
+    /// Stops `channel`. Its output stays at its last level.
+    pub fn disable(&mut self, channel: Channel) {
+        self.registers.CTL.and_mask(!((Ctl::Enable as u32) << channel.shift()));
+    }
+
+    /// Returns the period of `channel`, in clocks.
+    pub fn range(&mut self, channel: Channel) -> u32 {
+        self.range_register(channel).read()
+    }
+
+    /// Sets the number of clocks `channel` is high per period, out of its
+    /// range.
+    pub fn set_duty(&mut self, channel: Channel, data: u32) {
+        self.data_register(channel).write(data);
+    }
+
+    /// Sets the duty cycle of `channel` to `percent` percent, clamped to 100.
+    pub fn set_duty_percent(&mut self, channel: Channel, percent: u32) {
+        let data = duty(self.range(channel), percent);
+        self.set_duty(channel, data);
+    }
+
+    /// Switches both channels to being fed from the FIFO, for audio output:
+    /// each is started in mark-space mode with a period of `range` clocks,
+    /// so that samples are `range` wide and played at `clock / range` Hz.
+    ///
+    /// The FIFO is shared: with both channels enabled, words written to it
+    /// alternate between `Pwm0` and `Pwm1` (left and right).
+    pub fn enable_fifo(&mut self, range: u32) {
+        self.registers.CTL.write(0);
+        self.registers.RNG1.write(range);
+        self.registers.RNG2.write(range);
+        self.registers.STA.write(Sta::WriteError as u32 | Sta::ReadError as u32
+            | Sta::BusError as u32);
+
+        let channel = Ctl::Enable as u32 | Ctl::UseFifo as u32 | Ctl::MarkSpace as u32;
+        self.registers.CTL.write(Ctl::ClearFifo as u32);
+        self.registers.CTL.write(channel << Channel::Pwm0.shift()
+            | channel << Channel::Pwm1.shift());
+    }
+
+    /// Returns `true` if the FIFO cannot take another sample.
+    pub fn is_fifo_full(&self) -> bool {
+        self.registers.STA.has_mask(Sta::FifoFull as u32)
+    }
+
+    /// Writes `samples` to the FIFO, blocking while it is full. Each sample
+    /// must be lower than the range given to `enable_fifo()`.
+    pub fn write_samples(&mut self, samples: &[u32]) {
+        for &sample in samples {
+            while self.is_fifo_full() {}
+            self.registers.FIF1.write(sample);
+        }
+    }
+
+    /// Enables or disables DMA requests: while enabled, a DMA engine can
+    /// keep the FIFO fed by writing to `FIFO_BUS_ADDR`, paced by the PWM
+    /// DREQ.
+    pub fn set_dma(&mut self, enabled: bool) {
+        if enabled {
+            self.registers.DMAC.write(DMAC_ENABLE | DMAC_THRESHOLDS);
+        } else {
+            self.registers.DMAC.write(DMAC_THRESHOLDS);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{divider, duty, Error};
+
+    #[test]
+    fn test_divider() {
+        assert_eq!(divider(19_200_000), Ok((1, 0)));
+        assert_eq!(divider(9_600_000), Ok((2, 0)));
+        assert_eq!(divider(100_000), Ok((192, 0)));
+        // 19.2 MHz / 1 MHz = 19 + 819.2 / 4096
+        assert_eq!(divider(1_000_000), Ok((19, 819)));
+        assert_eq!(divider(4_688), Ok((0xfff, 2306)));
+
+        assert_eq!(divider(0), Err(Error::ClockTooSlow));
+        assert_eq!(divider(4_687), Err(Error::ClockTooSlow));
+        assert_eq!(divider(10_000_000), Err(Error::ClockTooFast));
+        assert_eq!(divider(19_200_001), Err(Error::ClockTooFast));
+        assert_eq!(divider(core::u32::MAX), Err(Error::ClockTooFast));
+    }
+
+    #[test]
+    fn test_duty() {
+        assert_eq!(duty(1000, 0), 0);
+        assert_eq!(duty(1000, 25), 250);
+        assert_eq!(duty(1000, 100), 1000);
+        assert_eq!(duty(1000, 150), 1000);
+        assert_eq!(duty(3, 50), 1);
+        assert_eq!(duty(core::u32::MAX, 100), core::u32::MAX);
+    }
+}
@@ -0,0 +1,109 @@
+use core::time::Duration;
+
+use shim::const_assert_size;
+
+use volatile::prelude::*;
+use volatile::{Reserved, Volatile};
+
+use crate::common::IO_BASE;
+use crate::timer;
+
+/// The base address for the `RNG` registers.
+const RNG_REG_BASE: usize = IO_BASE + 0x104000;
+
+/// The number of generator cycles discarded after the generator is enabled,
+/// before words are made available.
+pub const WARMUP_COUNT: u32 = 0x40000;
+
+/// Enum representing bit fields of the `RNG_CTRL` register.
+#[repr(u32)]
+enum Ctrl {
+    Enable = 1,
+}
+
+/// Enum representing bit fields of the `RNG_INT_MASK` register.
+#[repr(u32)]
+enum IntMask {
+    Off = 1,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    CTRL: Volatile<u32>,
+    /// Warm-up count in bits 0-19, words in the FIFO in bits 24-31.
+    STATUS: Volatile<u32>,
+    DATA: Volatile<u32>,
+    __r0: Reserved<u32>,
+    INT_MASK: Volatile<u32>,
+}
+
+const_assert_size!(Registers, 0x7E104014 - 0x7E104000);
+
+/// The BCM2837 hardware random number generator.
+pub struct Rng {
+    registers: &'static mut Registers,
+}
+
+impl Rng {
+    /// Returns a handle to the generator, enabling it with a warm-up of
+    /// `WARMUP_COUNT` cycles if it is not running yet. Its interrupt is
+    /// masked: words are polled for.
+    pub fn new() -> Rng {
+        let registers = unsafe { &mut *(RNG_REG_BASE as *mut Registers) };
+        registers.INT_MASK.or_mask(IntMask::Off as u32);
+        if !registers.CTRL.has_mask(Ctrl::Enable as u32) {
+            registers.STATUS.write(WARMUP_COUNT);
+            registers.CTRL.write(Ctrl::Enable as u32);
+        }
+        Rng { registers }
+    }
+
+    /// Returns the number of random words waiting in the FIFO. It stays 0
+    /// during the warm-up.
+    pub fn available(&self) -> u32 {
+        self.registers.STATUS.read() >> 24
+    }
+
+    /// Returns a random word, or `None` if the FIFO is empty. This method
+    /// does not block.
+    pub fn try_next_u32(&mut self) -> Option<u32> {
+        if self.available() == 0 {
+            return None;
+        }
+        Some(self.registers.DATA.read())
+    }
+
+    /// Returns a random word, waiting at most `timeout` for one. Returns
+    /// `None` on timeout, which is what happens when the generator is absent
+    /// or still warming up.
+    pub fn next_u32_timeout(&mut self, timeout: Duration) -> Option<u32> {
+        let beginning = timer::current_time();
+        loop {
+            if let Some(word) = self.try_next_u32() {
+                return Some(word);
+            }
+            if timer::current_time() > beginning + timeout {
+                return None;
+            }
+        }
+    }
+
+    /// Returns a random word. Blocks indefinitely until one is available.
+    pub fn next_u32(&mut self) -> u32 {
+        loop {
+            if let Some(word) = self.try_next_u32() {
+                return word;
+            }
+        }
+    }
+
+    /// Fills `buf` with random bytes. Blocks until enough words have been
+    /// generated.
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(4) {
+            let word = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use shim::const_assert_size;
+
+use volatile::prelude::*;
+use volatile::Volatile;
+
+use crate::common::{CORE_CLOCK, IO_BASE};
+use crate::gpio::{Function, Gpio};
+
+/// The base address for the `SPI0` registers.
+const SPI0_REG_BASE: usize = IO_BASE + 0x204000;
+
+/// The default SCLK frequency.
+pub const DEFAULT_SPEED: u32 = 1_000_000;
+
+/// The size of the transmit and receive FIFOs, in bytes.
+const FIFO_SIZE: usize = 64;
+
+/// Enum representing bit fields of the `CS` register.
+#[repr(u32)]
+enum CsStatus {
+    ChipSelect = 0b11,
+    Cpha = 1 << 2,
+    Cpol = 1 << 3,
+    ClearFifos = 0b11 << 4,
+    Active = 1 << 7,
+    InterruptDone = 1 << 9,
+    InterruptRx = 1 << 10,
+    Done = 1 << 16,
+    RxData = 1 << 17,
+    TxData = 1 << 18,
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    // Declare the SPI registers from page 152.
+    CS: Volatile<u32>,
+    FIFO: Volatile<u32>,
+    CLK: Volatile<u32>,
+    DLEN: Volatile<u32>,
+    LTOH: Volatile<u32>,
+    DC: Volatile<u32>,
+}
+
+const_assert_size!(Registers, 0x7E204018 - 0x7E204000);
+
+/// The chip select line asserted during transfers.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ChipSelect {
+    /// `CE0`, on GPIO pin 8.
+    Ce0 = 0,
+    /// `CE1`, on GPIO pin 7.
+    Ce1 = 1,
+    /// No chip select line: the device is selected by other means.
+    None = 2,
+}
+
+/// The clock polarity (CPOL) and phase (CPHA) of transfers.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+    /// Clock idle low, data sampled on the rising edge.
+    Mode0,
+    /// Clock idle low, data sampled on the falling edge.
+    Mode1,
+    /// Clock idle high, data sampled on the falling edge.
+    Mode2,
+    /// Clock idle high, data sampled on the rising edge.
+    Mode3,
+}
+
+impl Mode {
+    /// Returns the `CPOL` and `CPHA` bits of the mode.
+    fn bits(self) -> u32 {
+        match self {
+            Mode::Mode0 => 0,
+            Mode::Mode1 => CsStatus::Cpha as u32,
+            Mode::Mode2 => CsStatus::Cpol as u32,
+            Mode::Mode3 => CsStatus::Cpol as u32 | CsStatus::Cpha as u32,
+        }
+    }
+}
+
+impl ChipSelect {
+    /// Returns `cs`, a `CS` register value, with the chip select line set to
+    /// `self`.
+    fn apply(self, cs: u32) -> u32 {
+        cs & !(CsStatus::ChipSelect as u32) | self as u32
+    }
+}
+
+impl Mode {
+    /// Returns `cs`, a `CS` register value, with the clock polarity and phase
+    /// of `self`.
+    fn apply(self, cs: u32) -> u32 {
+        cs & !(CsStatus::Cpol as u32 | CsStatus::Cpha as u32) | self.bits()
+    }
+}
+
+/// Returns the clock divider making SCLK run at most at `hz`: the smallest
+/// even divider that is large enough, since the controller rounds odd ones
+/// down, within the range of `CLK`. A null `hz` gets the slowest clock.
+fn divider(hz: u32) -> u16 {
+    if hz == 0 {
+        return 0xfffe;
+    }
+    let div = CORE_CLOCK / hz + (CORE_CLOCK % hz != 0) as u32;
+    let div = div + (div & 1);
+    div.max(2).min(0xfffe) as u16
+}
+
+/// A transfer in interrupt mode, started with `Spi::start()` and advanced by
+/// `Spi::service()` from the `Interrupt::Spi` handler. It owns its buffer,
+/// `B`, so that the handler can hold it: a `Vec<u8>` or a `&'static mut [u8]`,
+/// for instance.
+pub struct Transfer<B> {
+    buf: B,
+    len: usize,
+    sent: usize,
+    received: usize,
+}
+
+impl<B: AsMut<[u8]>> Transfer<B> {
+    /// Returns `true` once every byte has been sent and received.
+    pub fn is_done(&self) -> bool {
+        self.received == self.len
+    }
+
+    /// Returns the buffer of the transfer. Once the transfer is done, it
+    /// holds the bytes received.
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+/// The Raspberry Pi's `SPI0` master.
+pub struct Spi {
+    registers: &'static mut Registers,
+}
+
+impl Spi {
+    /// Initializes `SPI0` in mode 0 at `DEFAULT_SPEED` with `CE0` as chip
+    /// select, setting GPIO pins 7 to 11 to alternative function 0
+    /// (CE1/CE0/MISO/MOSI/SCLK).
+    pub fn new() -> Spi {
+        let registers = unsafe { &mut *(SPI0_REG_BASE as *mut Registers) };
+
+        for pin in 7..12 {
+            Gpio::new(pin).into_alt(Function::Alt0);
+        }
+        registers.CS.write(CsStatus::ClearFifos as u32);
+
+        let mut spi = Spi { registers };
+        spi.set_speed(DEFAULT_SPEED);
+        spi
+    }
+
+    /// Selects the chip select line asserted during transfers.
+    pub fn set_chip_select(&mut self, cs: ChipSelect) {
+        let value = cs.apply(self.registers.CS.read());
+        self.registers.CS.write(value);
+    }
+
+    /// Sets the clock polarity and phase of transfers.
+    pub fn set_mode(&mut self, mode: Mode) {
+        let value = mode.apply(self.registers.CS.read());
+        self.registers.CS.write(value);
+    }
+
+    /// Sets the clock divider: SCLK runs at `CORE_CLOCK / div`. The divider is
+    /// rounded down to an even number by the controller, and 0 stands for
+    /// 65536.
+    pub fn set_clock_divider(&mut self, div: u16) {
+        self.registers.CLK.write(div as u32);
+    }
+
+    /// Sets the divider so that SCLK runs at most at `hz`.
+    pub fn set_speed(&mut self, hz: u32) {
+        self.set_clock_divider(divider(hz));
+    }
+
+    /// Sends the bytes of `buf` while replacing them with the bytes received,
+    /// asserting the chip select line for the whole transfer. Blocks until
+    /// the transfer completes.
+    pub fn transfer(&mut self, buf: &mut [u8]) {
+        self.registers.CS.or_mask(CsStatus::ClearFifos as u32 | CsStatus::Active as u32);
+
+        let (mut sent, mut received) = (0, 0);
+        while received < buf.len() {
+            // Never have more than a FIFO's worth of bytes in flight, so that
+            // the receive FIFO cannot overflow.
+            while sent < buf.len() && sent - received < FIFO_SIZE
+                && self.registers.CS.has_mask(CsStatus::TxData as u32)
+            {
+                self.registers.FIFO.write(buf[sent] as u32);
+                sent += 1;
+            }
+            while received < sent && self.registers.CS.has_mask(CsStatus::RxData as u32) {
+                buf[received] = self.registers.FIFO.read() as u8;
+                received += 1;
+            }
+        }
+
+        while !self.registers.CS.has_mask(CsStatus::Done as u32) {}
+        self.registers.CS.and_mask(!(CsStatus::Active as u32));
+    }
+
+    /// Starts a full-duplex transfer of `buf` in interrupt mode and returns
+    /// it. The controller raises `Interrupt::Spi` whenever it needs to be
+    /// serviced; the handler must then call `service()` until the returned
+    /// transfer is done. `Interrupt::Spi` must be enabled in the interrupt
+    /// controller for the transfer to make progress.
+    pub fn start<B: AsMut<[u8]>>(&mut self, mut buf: B) -> Transfer<B> {
+        // With an empty transmit FIFO, the done interrupt fires right away
+        // and the first call to `service()` fills it.
+        self.registers.CS.or_mask(CsStatus::ClearFifos as u32 | CsStatus::Active as u32
+            | CsStatus::InterruptDone as u32 | CsStatus::InterruptRx as u32);
+        let len = buf.as_mut().len();
+        Transfer { buf, len, sent: 0, received: 0 }
+    }
+
+    /// Moves bytes between the FIFOs and `transfer`. Once the transfer is
+    /// done, the chip select line is released and the interrupts disabled.
+    /// Returns `true` if the transfer is done.
+    pub fn service<B: AsMut<[u8]>>(&mut self, transfer: &mut Transfer<B>) -> bool {
+        let buf = transfer.buf.as_mut();
+        while transfer.received < transfer.sent
+            && self.registers.CS.has_mask(CsStatus::RxData as u32)
+        {
+            buf[transfer.received] = self.registers.FIFO.read() as u8;
+            transfer.received += 1;
+        }
+        while transfer.sent < buf.len() && transfer.sent - transfer.received < FIFO_SIZE
+            && self.registers.CS.has_mask(CsStatus::TxData as u32)
+        {
+            self.registers.FIFO.write(buf[transfer.sent] as u32);
+            transfer.sent += 1;
+        }
+
+        if transfer.is_done() && self.registers.CS.has_mask(CsStatus::Done as u32) {
+            self.registers.CS.and_mask(!(CsStatus::Active as u32 | CsStatus::InterruptDone as u32
+                | CsStatus::InterruptRx as u32));
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{divider, ChipSelect, Mode};
+
+    #[test]
+    fn test_divider() {
+        // 250 MHz core clock.
+        assert_eq!(divider(1_000_000), 250);
+        assert_eq!(divider(125_000_000), 2);
+        // Rounded up to the next even divider: never faster than asked.
+        assert_eq!(divider(3_000_000), 84);
+        assert_eq!(divider(2_000_000), 126);
+        assert_eq!(divider(7_000_000), 36);
+        // Clamped to what the controller supports.
+        assert_eq!(divider(250_000_000), 2);
+        assert_eq!(divider(core::u32::MAX), 2);
+        assert_eq!(divider(1_000), 0xfffe);
+        assert_eq!(divider(0), 0xfffe);
+    }
+
+    #[test]
+    fn test_cs_fields() {
+        // TA, CPOL and CE1 set.
+        let cs = 1 << 7 | 1 << 3 | 0b01;
+        assert_eq!(ChipSelect::Ce0.apply(cs), 1 << 7 | 1 << 3);
+        assert_eq!(ChipSelect::None.apply(cs), 1 << 7 | 1 << 3 | 0b10);
+        assert_eq!(Mode::Mode0.apply(cs), 1 << 7 | 0b01);
+        assert_eq!(Mode::Mode1.apply(cs), 1 << 7 | 1 << 2 | 0b01);
+        assert_eq!(Mode::Mode3.apply(cs), 1 << 7 | 1 << 3 | 1 << 2 | 0b01);
+        assert_eq!(Mode::Mode2.apply(0), 1 << 3);
+    }
+}
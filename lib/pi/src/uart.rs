@@ -22,6 +22,14 @@ const AUX_ENABLES: *mut Volatile<u8> = (IO_BASE + 0x215004) as *mut Volatile<u8>
 enum LsrStatus {
     DataReady = 1,
     TxAvailable = 1 << 5,
+    TxIdle = 1 << 6,
+}
+
+/// Enum representing bit fields of the `AUX_MU_IER_REG` register. Note that
+/// the BCM2837 documentation has the receive and transmit bits swapped.
+#[repr(u8)]
+enum IerStatus {
+    RxInterrupt = 1,
 }
 
 #[repr(C)]
@@ -70,8 +78,7 @@ impl MiniUart {
         };
 
         // Implement remaining mini UART initialization.
-        Gpio::new(14).into_alt(Function::Alt5);
-        Gpio::new(15).into_alt(Function::Alt5);
+        MiniUart::claim_pins();
         registers.LCR.write(3); // 011
         registers.BAUD.write(270); 
         registers.IIR.write(6); // 110
@@ -79,11 +86,29 @@ impl MiniUart {
         MiniUart {registers, timeout: None}
     }
 
+    /// Sets GPIO pins 14 and 15 to alternative function 5 (TXD1/RXD1), e.g.
+    /// to hand them back to an initialized mini UART after the PL011 took
+    /// them over.
+    pub fn claim_pins() {
+        Gpio::new(14).into_alt(Function::Alt5);
+        Gpio::new(15).into_alt(Function::Alt5);
+    }
+
     /// Set the read timeout to `t` duration.
     pub fn set_read_timeout(&mut self, t: Duration) {
         self.timeout = Some(t);
     }
 
+    /// Enables or disables the receive interrupt. While enabled, the UART
+    /// raises `Interrupt::Aux` as long as its receive FIFO is not empty.
+    pub fn set_rx_interrupt(&mut self, enabled: bool) {
+        if enabled {
+            self.registers.IER.or_mask(IerStatus::RxInterrupt as u32);
+        } else {
+            self.registers.IER.and_mask(!(IerStatus::RxInterrupt as u32));
+        }
+    }
+
     /// Write the byte `byte`. This method blocks until there is space available
     /// in the output FIFO.
     pub fn write_byte(&mut self, byte: u8) {
@@ -182,7 +207,9 @@ mod uart_io {
             }
             return Ok(data.len());
         }
+        /// Waits until every written byte has been sent.
         fn flush(&mut self) -> Result<(), io::Error> {
+            while !self.registers.LSR.has_mask(LsrStatus::TxIdle as u32) {}
             return Ok(());
         }
     }